@@ -1,13 +1,18 @@
-use std::borrow::Borrow;
+use std::collections::TryReserveError;
 use std::collections::hash_map::RandomState;
 use std::hash::{BuildHasher, Hash, Hasher};
+use std::iter::FromIterator;
 use std::mem;
 use std::ops::Index;
 use std::ptr;
+use std::slice;
 use std::sync::Arc;
+use std::vec;
 
 use rand;
 
+use collections::equivalent::Equivalent;
+
 /// An optionally persistent map implemented as a Hash Array Mapped Trie.
 #[derive(Clone)]
 pub struct CloneMap<K, V, S = RandomState>
@@ -20,6 +25,7 @@ where
     seed: u32,
     hash_builder: S,
     root: Arc<CNode<K, V>>,
+    size: usize,
 }
 
 
@@ -30,7 +36,13 @@ where
     V: Clone,
 {
     C(CNode<K, V>),
-    M(CloneMap<K, V>),
+
+    /// Two or more entries that share a full 64-bit hash. These are rare
+    /// enough (and bounded by the actual number of colliding keys, rather
+    /// than recursively rehashed) that a linear scan over a small `Vec` is
+    /// cheaper than another level of trie.
+    Collision(u64, Vec<Store<K, V>>),
+
     S(Store<K, V>),
 }
 
@@ -100,6 +112,7 @@ where
             seed: rand::random(),
             hash_builder: RandomState::new(),
             root: Arc::new(CNode::new()),
+            size: 0,
         }
     }
 }
@@ -113,7 +126,7 @@ where
 {
     fn hash<Q: ?Sized>(&self, q: &Q) -> u64
     where
-        Q: Hash + Eq,
+        Q: Hash,
     {
         let mut hasher = self.hash_builder.build_hasher();
         hasher.write_u32(self.seed);
@@ -122,23 +135,38 @@ where
     }
 
 
-    // TODO: compact-on-remove must be implemented before this.
-    // TODO: update examples for other methods once this is enabled.
-    // /// Returns true if the map contains no elements.
-    // ///
-    // /// # Examples
-    // ///
-    // /// ```
-    // /// use ripl::collections::CloneMap;
-    // ///
-    // /// let mut a = CloneMap::new();
-    // /// assert!(a.is_empty());
-    // /// a.insert(1, "a");
-    // /// assert!(!a.is_empty());
-    // /// ```
-    // pub fn is_empty(&self) -> bool {
-    //     self.root.bitmap == 0
-    // }
+    /// Returns the number of key-value pairs in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let mut a = CloneMap::new();
+    /// assert_eq!(a.len(), 0);
+    /// a.insert(1, "a");
+    /// assert_eq!(a.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+
+    /// Returns true if the map contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let mut a = CloneMap::new();
+    /// assert!(a.is_empty());
+    /// a.insert(1, "a");
+    /// assert!(!a.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
 
 
     /// Clears the map, removing all key-value pairs. Keeps the allocated memory
@@ -152,21 +180,20 @@ where
     /// let mut a = CloneMap::new();
     /// a.insert(1, "a");
     /// a.clear();
-    /// // assert!(a.is_empty());
+    /// assert!(a.is_empty());
     /// ```
     pub fn clear(&mut self) {
         self.root = Arc::new(CNode::new());
+        self.size = 0;
     }
 
 
     /// Returns a reference to the value corresponding to the key.
     ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
-    /// the key type.
-    ///
-    /// [`Eq`]: doc.rust-lang.org/std/cmp/trait.Eq.html
-    /// [`Hash`]: doc.rust-lang.org/std/hash/trait.Hash.html
+    /// The key may be any type that is [`Equivalent`](Equivalent) to the
+    /// map's key type -- every `Borrow`ed form qualifies automatically, and
+    /// so does any type that hashes the same way but compares itself via a
+    /// custom `Equivalent` impl.
     ///
     /// # Examples
     ///
@@ -180,23 +207,49 @@ where
     /// ```
     pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K>,
     {
         let hash = self.hash(key);
         self.root.get(hash, key, 0, self.branch_power)
     }
 
 
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// The key may be any type that is [`Equivalent`](Equivalent) to the
+    /// map's key type -- every `Borrow`ed form qualifies automatically, and
+    /// so does any type that hashes the same way but compares itself via a
+    /// custom `Equivalent` impl.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let mut map = CloneMap::new();
+    /// map.insert(1, "a");
+    /// if let Some(val) = map.get_mut(&1) {
+    ///     *val = "b";
+    /// }
+    /// assert_eq!(map.get(&1), Some(&"b"));
+    /// ```
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K>,
+    {
+        let hash = self.hash(key);
+        let w = self.branch_power;
+        Arc::make_mut(&mut self.root).get_mut(hash, key, 0, w)
+    }
+
+
     /// Removes a key from the map, returning the value at the key if the key
     /// was previously in the map.
     ///
-    /// The key may be any borrowed form of the map's key type, but
-    /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
-    /// the key type.
-    ///
-    /// [`Eq`]: doc.rust-lang.org/std/cmp/trait.Eq.html
-    /// [`Hash`]: doc.rust-lang.org/std/hash/trait.Hash.html
+    /// The key may be any type that is [`Equivalent`](Equivalent) to the
+    /// map's key type -- every `Borrow`ed form qualifies automatically, and
+    /// so does any type that hashes the same way but compares itself via a
+    /// custom `Equivalent` impl.
     ///
     /// # Examples
     ///
@@ -210,12 +263,15 @@ where
     /// ```
     pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K>,
     {
         let hash = self.hash(key);
         let mut root = Arc::make_mut(&mut self.root);
-        root.remove(hash, key, 0, self.branch_power)
+        let val = root.remove(hash, key, 0, self.branch_power);
+        if val.is_some() {
+            self.size -= 1;
+        }
+        val
     }
 
 
@@ -242,15 +298,426 @@ where
     pub fn insert(&mut self, key: K, val: V) -> Option<V> {
         let hash = self.hash(&key);
         let mut root = Arc::make_mut(&mut self.root);
-        root.insert(hash, key, val, 0, self.branch_power)
+        let old = root.insert(hash, key, val, 0, self.branch_power);
+        if old.is_none() {
+            self.size += 1;
+        }
+        old
+    }
+
+
+    /// Like [`insert`](CloneMap::insert), but reports a failure to grow a
+    /// branch vector as `Err(TryReserveError)` rather than aborting the
+    /// process.
+    ///
+    /// Every branch vector grown on the write path is reserved with
+    /// [`Vec::try_reserve`] before anything is moved. The `Arc` allocation
+    /// of a new leaf and the `Arc::make_mut` copy of a shared node on the
+    /// path to the key are not fallible: a failure there still aborts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let mut map = CloneMap::new();
+    /// assert_eq!(map.try_insert(37, "a"), Ok(None));
+    /// assert_eq!(map.try_insert(37, "b"), Ok(Some("a")));
+    /// ```
+    pub fn try_insert(&mut self, key: K, val: V) -> Result<Option<V>, TryReserveError> {
+        let hash = self.hash(&key);
+        let mut root = Arc::make_mut(&mut self.root);
+        let old = root.try_insert(hash, key, val, 0, self.branch_power)?;
+        if old.is_none() {
+            self.size += 1;
+        }
+        Ok(old)
+    }
+
+
+    /// Like [`remove`](CloneMap::remove), but with a `Result` signature
+    /// matching [`try_insert`](CloneMap::try_insert).
+    ///
+    /// Removal never grows the trie, so in practice this cannot fail; it
+    /// exists purely so callers don't have to special-case a "try" map API
+    /// that can allocate on one mutator but not the other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let mut map = CloneMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.try_remove(&1), Ok(Some("a")));
+    /// ```
+    pub fn try_remove<Q: ?Sized>(&mut self, key: &Q) -> Result<Option<V>, TryReserveError>
+    where
+        Q: Hash + Equivalent<K>,
+    {
+        Ok(self.remove(key))
+    }
+
+
+    /// Returns a depth-first iterator over the key-value pairs of the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let mut a = CloneMap::new();
+    /// a.insert(1, "a");
+    /// assert_eq!(a.iter().collect::<Vec<_>>(), vec![(&1, &"a")]);
+    /// ```
+    pub fn iter(&self) -> Iter<K, V> {
+        Iter { stack: vec![self.root.branches.iter()], bucket: [].iter() }
+    }
+
+
+    /// Returns an iterator over the keys of the map, in the same order as
+    /// [`iter`](CloneMap::iter).
+    pub fn keys(&self) -> IterKeys<K, V> {
+        IterKeys { inner: self.iter() }
+    }
+
+
+    /// Returns an iterator over the values of the map, in the same order as
+    /// [`iter`](CloneMap::iter).
+    pub fn values(&self) -> IterValues<K, V> {
+        IterValues { inner: self.iter() }
+    }
+
+
+    /// Gets the given key's corresponding entry in the map for in-place
+    /// manipulation.
+    ///
+    /// The trie is walked once to locate the key, whether or not it turns
+    /// out to be present; the returned [`Entry`] finishes the job -- reading
+    /// or replacing an existing value, or inserting a fresh one -- without
+    /// walking the trie again. This avoids the double traversal of calling
+    /// [`get`](CloneMap::get) followed by [`insert`](CloneMap::insert), and
+    /// makes in-place aggregations like counters cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let mut counts: CloneMap<&str, usize> = CloneMap::new();
+    /// *counts.entry("a").or_insert(0) += 1;
+    /// *counts.entry("a").or_insert(0) += 1;
+    /// assert_eq!(counts.get("a"), Some(&2));
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<K, V> {
+        let hash = self.hash(&key);
+        let w = self.branch_power;
+        let size = &mut self.size;
+        let root = Arc::make_mut(&mut self.root);
+
+        match root.entry(hash, &key, 0, w) {
+            RawEntry::Occupied(val) => Entry::Occupied(OccupiedEntry { val: val }),
+            RawEntry::Vacant(slot) => Entry::Vacant(VacantEntry {
+                key: key,
+                hash: hash,
+                w: w,
+                size: size,
+                slot: slot,
+            }),
+        }
+    }
+}
+
+
+// Persistent API
+// --------------------------------------------------
+
+impl<K, V, S> CloneMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Returns a new map with the key-value pair inserted, leaving `self`
+    /// unchanged.
+    ///
+    /// Because `self` is not mutated, the clone shares every subtree that
+    /// the insertion doesn't touch: `Arc::make_mut` only copies the path
+    /// from the root down to the new leaf.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let a = CloneMap::new();
+    /// let b = a.insert_persistent(1, "a");
+    /// assert_eq!(a.get(&1), None);
+    /// assert_eq!(b.get(&1), Some(&"a"));
+    /// ```
+    pub fn insert_persistent(&self, key: K, val: V) -> CloneMap<K, V, S> {
+        let mut next = self.clone();
+        next.insert(key, val);
+        next
+    }
+
+
+    /// Returns a new map with the key removed, along with the value that was
+    /// removed, leaving `self` unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::CloneMap;
+    ///
+    /// let a = CloneMap::new();
+    /// let a = a.insert_persistent(1, "a");
+    /// let (b, val) = a.remove_persistent(&1);
+    /// assert_eq!(val, Some("a"));
+    /// assert_eq!(a.get(&1), Some(&"a"));
+    /// assert_eq!(b.get(&1), None);
+    /// ```
+    pub fn remove_persistent<Q: ?Sized>(&self, key: &Q) -> (CloneMap<K, V, S>, Option<V>)
+    where
+        Q: Hash + Equivalent<K>,
+    {
+        let mut next = self.clone();
+        let val = next.remove(key);
+        (next, val)
+    }
+}
+
+
+// Entry API
+// --------------------------------------------------
+
+/// A view into a single entry in a [`CloneMap`], which may be either
+/// vacant or occupied.
+///
+/// Created by [`CloneMap::entry`].
+pub enum Entry<'a, K: 'a, V: 'a>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    Occupied(OccupiedEntry<'a, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Ensures a value is in the entry by inserting `default` if it is
+    /// vacant, then returns a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.val,
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+
+    /// Ensures a value is in the entry by inserting the result of `default`
+    /// if it is vacant, then returns a mutable reference to the value.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(e) => e.val,
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+
+    /// Ensures a value is in the entry by inserting the result of `default`
+    /// (which is given a reference to the key) if it is vacant, then
+    /// returns a mutable reference to the value.
+    pub fn or_insert_with_key<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce(&K) -> V,
+    {
+        match self {
+            Entry::Occupied(e) => e.val,
+            Entry::Vacant(e) => {
+                let val = default(&e.key);
+                e.insert(val)
+            },
+        }
+    }
+
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    pub fn and_modify<F>(self, f: F) -> Entry<'a, K, V>
+    where
+        F: FnOnce(&mut V),
+    {
+        match self {
+            Entry::Occupied(e) => {
+                f(e.val);
+                Entry::Occupied(e)
+            },
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone + Default,
+{
+    /// Ensures a value is in the entry by inserting its type's default
+    /// value if it is vacant, then returns a mutable reference to the
+    /// value.
+    pub fn or_default(self) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.val,
+            Entry::Vacant(e) => e.insert(V::default()),
+        }
+    }
+}
+
+
+/// A view into an occupied entry in a [`CloneMap`]. Part of the [`Entry`]
+/// enum.
+pub struct OccupiedEntry<'a, V: 'a> {
+    val: &'a mut V,
+}
+
+
+/// A view into a vacant entry in a [`CloneMap`]. Part of the [`Entry`]
+/// enum.
+pub struct VacantEntry<'a, K: 'a, V: 'a>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    key: K,
+    hash: u64,
+    w: u32,
+    size: &'a mut usize,
+    slot: VacantSlot<'a, K, V>,
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Gets a reference to the key that would be used when inserting a
+    /// value through this entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+
+    /// Sets the value of the entry, returning a mutable reference to it.
+    ///
+    /// Because [`entry`](CloneMap::entry) already located the exact spot
+    /// the key belongs at, this finishes the insertion in place: a plain
+    /// vacancy or an existing collision bucket are filled directly, and
+    /// only a full/partial-hash conflict with an existing leaf needs the
+    /// handful of extra steps `CNode::insert` would take to split it --
+    /// none of it requires walking the trie again from the root.
+    pub fn insert(self, val: V) -> &'a mut V {
+        *self.size += 1;
+
+        let hash = self.hash;
+        let key = self.key;
+
+        match self.slot {
+            VacantSlot::Empty(node, flag, pos) => {
+                let branch = Arc::new(Branch::S(Store::new(hash, key, val)));
+                node.branches.insert(pos, branch);
+                node.bitmap |= flag;
+
+                // SAFETY: the `Arc` we just inserted was freshly created, so
+                // it is exclusively owned and `Arc::get_mut` cannot fail.
+                match *Arc::get_mut(&mut node.branches[pos]).unwrap() {
+                    Branch::S(ref mut s) => &mut s.val,
+                    _ => unreachable!(),
+                }
+            },
+
+            VacantSlot::Bucket(bucket) => {
+                bucket.push(Store::new(hash, key, val));
+                let last = bucket.len() - 1;
+                &mut bucket[last].val
+            },
+
+            VacantSlot::Split(branch, level) => {
+                let w = self.w;
+                let lookup_key = key.clone();
+
+                let (s_hash, s_key, s_val) = match *branch {
+                    Branch::S(ref s) => (s.hash, s.key.clone(), s.val.clone()),
+                    _ => unreachable!(),
+                };
+
+                *branch = if hash == s_hash {
+                    Branch::Collision(hash, vec![
+                        Store::new(s_hash, s_key, s_val),
+                        Store::new(hash, key, val),
+                    ])
+                } else {
+                    let mut c = CNode::new();
+                    c.insert(s_hash, s_key, s_val, level + w, w);
+                    c.insert(hash, key, val, level + w, w);
+                    Branch::C(c)
+                };
+
+                match *branch {
+                    Branch::Collision(_, ref mut bucket) => bucket
+                        .iter_mut()
+                        .find(|s| s.key == lookup_key)
+                        .map(|s| &mut s.val)
+                        .unwrap(),
+                    Branch::C(ref mut c) => c.get_mut(hash, &lookup_key, level + w, w).unwrap(),
+                    Branch::S(_) => unreachable!(),
+                }
+            },
+        }
     }
 }
 
 
+/// Where a vacant key belongs, as located by [`CNode::entry`].
+enum VacantSlot<'a, K: 'a, V: 'a>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// No branch occupies this bitmap position; insert a fresh `S` leaf.
+    Empty(&'a mut CNode<K, V>, u64, usize),
+
+    /// A full-hash collision bucket already lives here but doesn't hold this
+    /// key yet; push a fresh entry onto it.
+    Bucket(&'a mut Vec<Store<K, V>>),
+
+    /// An `S` leaf occupies this position under a different key; inserting
+    /// means splitting it, exactly as `CNode::insert` would.
+    Split(&'a mut Branch<K, V>, u32),
+}
+
+/// The result of locating a key in the trie for the [`Entry`] API.
+enum RawEntry<'a, K: 'a, V: 'a>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    Occupied(&'a mut V),
+    Vacant(VacantSlot<'a, K, V>),
+}
+
+
 impl<'a, K, Q, V, S> Index<&'a Q> for CloneMap<K, V, S>
 where
-    K: Hash + Eq + Clone + Borrow<Q>,
-    Q: Hash + Eq,
+    K: Hash + Eq + Clone,
+    Q: Hash + Equivalent<K> + ?Sized,
     V: Clone,
     S: BuildHasher,
 {
@@ -260,6 +727,156 @@ where
     }
 }
 
+
+impl<K, V> FromIterator<(K, V)> for CloneMap<K, V, RandomState>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> CloneMap<K, V, RandomState> {
+        let mut map = CloneMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+
+impl<K, V, S> Extend<(K, V)> for CloneMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (key, val) in iter {
+            self.insert(key, val);
+        }
+    }
+}
+
+
+impl<'a, K, V, S> IntoIterator for &'a CloneMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+
+impl<K, V, S> IntoIterator for CloneMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    type Item = (K, V);
+    type IntoIter = vec::IntoIter<(K, V)>;
+    fn into_iter(self) -> vec::IntoIter<(K, V)> {
+        // The trie doesn't support consuming traversal (branches may still
+        // be shared with other persistent clones), so we collect eagerly.
+        let pairs: Vec<(K, V)> = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        pairs.into_iter()
+    }
+}
+
+
+/// A depth-first iterator over the key-value pairs of a [`CloneMap`].
+///
+/// Created by [`CloneMap::iter`].
+pub struct Iter<'a, K: 'a, V: 'a>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    stack: Vec<slice::Iter<'a, Arc<Branch<K, V>>>>,
+    bucket: slice::Iter<'a, Store<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            if let Some(s) = self.bucket.next() {
+                return Some((&s.key, &s.val));
+            }
+
+            let next = match self.stack.last_mut() {
+                Some(top) => top.next(),
+                None => return None,
+            };
+
+            match next {
+                Some(branch) => {
+                    match **branch {
+                        Branch::S(ref s) => return Some((&s.key, &s.val)),
+                        Branch::C(ref c) => self.stack.push(c.branches.iter()),
+                        Branch::Collision(_, ref bucket) => self.bucket = bucket.iter(),
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+
+/// An iterator over the keys of a [`CloneMap`].
+///
+/// Created by [`CloneMap::keys`].
+pub struct IterKeys<'a, K: 'a, V: 'a>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for IterKeys<'a, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+}
+
+
+/// An iterator over the values of a [`CloneMap`].
+///
+/// Created by [`CloneMap::values`].
+pub struct IterValues<'a, K: 'a, V: 'a>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for IterValues<'a, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    type Item = &'a V;
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+}
+
 // CNode
 // --------------------------------------------------
 
@@ -305,8 +922,7 @@ where
     /// tree (the log of the branching factor).
     fn get<Q: ?Sized>(&self, hash: u64, key: &Q, level: u32, w: u32) -> Option<&V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K>,
     {
         let (flag, pos) = self.flagpos(hash, level, w);
 
@@ -320,13 +936,17 @@ where
         let branch = unsafe { self.branches.get_unchecked(pos) };
 
         match **branch {
-            // Recurse on M and C branches.
-            Branch::M(ref m) => m.get(key),
+            // Recurse on C branches.
             Branch::C(ref c) => c.get(hash, key, level + w, w),
 
+            // A collision bucket is scanned linearly for the key.
+            Branch::Collision(_, ref bucket) => {
+                bucket.iter().find(|s| key.equivalent(&s.key)).map(|s| &s.val)
+            },
+
             // S branches are leaves and may constain the key.
             Branch::S(ref s) => {
-                if s.key.borrow() == key {
+                if key.equivalent(&s.key) {
                     Some(&s.val)
                 } else {
                     None
@@ -346,8 +966,7 @@ where
     /// tree (the log of the branching factor).
     fn remove<Q: ?Sized>(&mut self, hash: u64, key: &Q, level: u32, w: u32) -> Option<V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K>,
     {
         let (flag, pos) = self.flagpos(hash, level, w);
 
@@ -362,17 +981,58 @@ where
             // SAFTEY: pos is safe because we've checked the flag against bitmap.
             let mut branch = unsafe { self.branches.get_unchecked_mut(pos) };
             let mut branch = Arc::make_mut(branch);
+            let branch_ptr = branch as *mut Branch<K, V>;
 
             match *branch {
-                // Recurse on M and C branches.
-                Branch::M(ref mut m) => return m.remove(key),
-                Branch::C(ref mut c) => return c.remove(hash, key, level + w, w),
+                // Recurse on C branches, then collapse the child into our
+                // own slot if it decayed down to a single leaf. This keeps
+                // the trie free of interior nodes with only one child.
+                Branch::C(ref mut c) => {
+                    let result = c.remove(hash, key, level + w, w);
+
+                    if c.branches.len() == 1 {
+                        if let Branch::S(_) = *c.branches[0] {
+                            let leaf = c.branches[0].clone();
+                            let leaf = match Arc::try_unwrap(leaf) {
+                                Ok(leaf) => leaf,
+                                Err(leaf) => (*leaf).clone(),
+                            };
+
+                            // SAFETY: `c` is not accessed again after this
+                            // point; the old `Branch::C` value is dropped.
+                            unsafe { ptr::replace(branch_ptr, leaf) };
+                        }
+                    }
+
+                    return result;
+                },
+
+                // A collision bucket: scan for the key, remove it if found,
+                // and collapse the bucket into a plain `S` branch once it is
+                // down to a single entry.
+                Branch::Collision(_, ref mut bucket) => {
+                    let found = bucket.iter().position(|s| key.equivalent(&s.key));
+                    let found = match found {
+                        Some(i) => i,
+                        None => return None,
+                    };
+                    let removed = bucket.remove(found);
+
+                    if bucket.len() == 1 {
+                        let leaf = Branch::S(bucket.remove(0));
+                        // SAFETY: `bucket` is not accessed again after this
+                        // point; the old `Branch::Collision` value is dropped.
+                        unsafe { ptr::replace(branch_ptr, leaf) };
+                    }
+
+                    return Some(removed.val);
+                },
 
                 // For S branches:
                 // - If the key doesn't match, we return None.
                 // - If the keys match, we break this scope to delete the branch.
                 Branch::S(ref mut s) => {
-                    if s.key.borrow() != key {
+                    if !key.equivalent(&s.key) {
                         return None;
                     }
                 },
@@ -380,7 +1040,6 @@ where
         }
 
         // Remove the S branch
-        // TODO: compact the tree if we only have one child.
         let branch = self.branches.remove(pos);
         let branch = Arc::try_unwrap(branch);
         match branch {
@@ -428,13 +1087,22 @@ where
         let branch_ptr = branch as *mut Branch<K, V>;
 
         match *branch {
-            // Recurse on M and C branches.
-            Branch::M(ref mut m) => return m.insert(key, val),
+            // Recurse on C branches.
             Branch::C(ref mut c) => return c.insert(hash, key, val, level + w, w),
 
+            // A collision bucket: replace the value if the key is already
+            // present, otherwise grow the bucket by one entry.
+            Branch::Collision(_, ref mut bucket) => {
+                if let Some(s) = bucket.iter_mut().find(|s| s.key == key) {
+                    return Some(mem::replace(&mut s.val, val));
+                }
+                bucket.push(Store::new(hash, key, val));
+                return None;
+            },
+
             // For S branches:
             // - If the key is a match, replace the value.
-            // - In the case of a hash collision, split into an M branch.
+            // - In the case of a hash collision, split into a `Collision` bucket.
             // - In the case of a partial collision, split into a C branch.
             Branch::S(ref mut s) => {
                 if key == s.key {
@@ -448,10 +1116,7 @@ where
                 let new_branch: Branch<K, V>;
 
                 if hash == s.hash {
-                    let mut m = CloneMap::with_branch_factor(1 << w);
-                    m.insert(s.key, s.val);
-                    m.insert(key, val);
-                    new_branch = Branch::M(m);
+                    new_branch = Branch::Collision(hash, vec![s, Store::new(hash, key, val)]);
                 } else {
                     let mut c = CNode::new();
                     c.insert(s.hash, s.key, s.val, level + w, w);
@@ -465,6 +1130,173 @@ where
             },
         }
     }
+
+
+    /// Like [`insert`](CNode::insert), but reserves capacity before growing
+    /// any branch vector and reports a failure to do so as `Err` instead of
+    /// aborting.
+    fn try_insert(
+        &mut self,
+        hash: u64,
+        key: K,
+        val: V,
+        level: u32,
+        w: u32,
+    ) -> Result<Option<V>, TryReserveError> {
+        let (flag, pos) = self.flagpos(hash, level, w);
+
+        // Simple case: insert if we have a vacancy.
+        if self.bitmap & flag == 0 {
+            self.branches.try_reserve(1)?;
+            self.branches
+                .insert(pos, Arc::new(Branch::S(Store::new(hash, key, val))));
+            self.bitmap |= flag;
+            return Ok(None);
+        }
+
+        // Otherwise we need to mutate an existing branch.
+        // This will clone the branch if we are not the exclusive owner.
+        // SAFTEY: pos is safe because we've checked the flag against bitmap.
+        let mut branch = unsafe { self.branches.get_unchecked_mut(pos) };
+        let mut branch = Arc::make_mut(branch);
+        let branch_ptr = branch as *mut Branch<K, V>;
+
+        match *branch {
+            // Recurse on C branches.
+            Branch::C(ref mut c) => return c.try_insert(hash, key, val, level + w, w),
+
+            // A collision bucket: replace the value if the key is already
+            // present, otherwise reserve room and grow the bucket.
+            Branch::Collision(_, ref mut bucket) => {
+                if let Some(s) = bucket.iter_mut().find(|s| s.key == key) {
+                    return Ok(Some(mem::replace(&mut s.val, val)));
+                }
+                bucket.try_reserve(1)?;
+                bucket.push(Store::new(hash, key, val));
+                return Ok(None);
+            },
+
+            // For S branches:
+            // - If the key is a match, replace the value.
+            // - In the case of a hash collision, split into a `Collision` bucket.
+            // - In the case of a partial collision, split into a C branch.
+            //
+            // Unlike `insert`, we clone `s.key`/`s.val` instead of moving
+            // them out: if a reservation below fails we can bail out with
+            // `s` left completely intact, rather than leaving the branch
+            // half-replaced.
+            Branch::S(ref mut s) => {
+                if key == s.key {
+                    let old_val = mem::replace(&mut s.val, val);
+                    return Ok(Some(old_val));
+                }
+
+                let new_branch = if hash == s.hash {
+                    let mut bucket = Vec::new();
+                    bucket.try_reserve(2)?;
+                    bucket.push(Store::new(hash, s.key.clone(), s.val.clone()));
+                    bucket.push(Store::new(hash, key, val));
+                    Branch::Collision(hash, bucket)
+                } else {
+                    let mut c = CNode::new();
+                    c.try_insert(s.hash, s.key.clone(), s.val.clone(), level + w, w)?;
+                    c.try_insert(hash, key, val, level + w, w)?;
+                    Branch::C(c)
+                };
+
+                // SAFTEY: ensure that the branch is replaced.
+                unsafe { ptr::replace(branch_ptr, new_branch) };
+                Ok(None)
+            },
+        }
+    }
+
+
+    /// Like [`get`](CNode::get), but returns a mutable reference.
+    fn get_mut<Q: ?Sized>(&mut self, hash: u64, key: &Q, level: u32, w: u32) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K>,
+    {
+        let (flag, pos) = self.flagpos(hash, level, w);
+
+        // Simple case: return None if there is no matching branch.
+        if self.bitmap & flag == 0 {
+            return None;
+        }
+
+        // SAFTEY: pos is safe because we've checked the flag against bitmap.
+        let branch = unsafe { self.branches.get_unchecked_mut(pos) };
+        let branch = Arc::make_mut(branch);
+
+        match *branch {
+            // Recurse on C branches.
+            Branch::C(ref mut c) => c.get_mut(hash, key, level + w, w),
+
+            // A collision bucket is scanned linearly for the key.
+            Branch::Collision(_, ref mut bucket) => {
+                bucket.iter_mut().find(|s| key.equivalent(&s.key)).map(|s| &mut s.val)
+            },
+
+            // S branches are leaves and may constain the key.
+            Branch::S(ref mut s) => {
+                if key.equivalent(&s.key) {
+                    Some(&mut s.val)
+                } else {
+                    None
+                }
+            },
+        }
+    }
+
+
+    /// Locates `key`'s position in the trie for the [`Entry`] API.
+    ///
+    /// Unlike `get`/`insert`, this neither reads nor mutates a value itself;
+    /// it walks the trie exactly once (path-copying via `Arc::make_mut`
+    /// just as `insert` does) and hands back a handle to the exact spot, so
+    /// that [`VacantEntry::insert`] can finish the job without walking the
+    /// trie again.
+    fn entry<'a>(&'a mut self, hash: u64, key: &K, level: u32, w: u32) -> RawEntry<'a, K, V> {
+        let (flag, pos) = self.flagpos(hash, level, w);
+
+        // Simple case: nothing lives at this position yet.
+        if self.bitmap & flag == 0 {
+            return RawEntry::Vacant(VacantSlot::Empty(self, flag, pos));
+        }
+
+        // SAFTEY: pos is safe because we've checked the flag against bitmap.
+        let branch = unsafe { self.branches.get_unchecked_mut(pos) };
+        let branch = Arc::make_mut(branch);
+        let branch_ptr = branch as *mut Branch<K, V>;
+
+        match *branch {
+            // Recurse on C branches.
+            Branch::C(ref mut c) => return c.entry(hash, key, level + w, w),
+
+            // A collision bucket: the key either already lives in the
+            // bucket, or belongs in it.
+            Branch::Collision(_, ref mut bucket) => {
+                if let Some(pos) = bucket.iter().position(|s| s.key == *key) {
+                    return RawEntry::Occupied(&mut bucket[pos].val);
+                }
+                return RawEntry::Vacant(VacantSlot::Bucket(bucket));
+            },
+
+            // An S branch already holds the key: occupied.
+            Branch::S(ref mut s) if s.key == *key => return RawEntry::Occupied(&mut s.val),
+
+            // An S branch under a different key needs to be split to make
+            // room for this one; handled below.
+            Branch::S(_) => {},
+        }
+
+        // SAFETY: the only arm that falls through to here is `Branch::S`
+        // with a non-matching key, and no reference into `*branch_ptr` is
+        // held past the match (every arm that keeps one returns). `level`
+        // identifies the depth `branch_ptr` was found at, for recursing
+        // into a fresh `C` branch if `VacantEntry::insert` needs to split.
+        RawEntry::Vacant(VacantSlot::Split(unsafe { &mut *branch_ptr }, level))
+    }
 }
 
 
@@ -559,4 +1391,254 @@ mod test {
             assert_eq!(m.get(&i), None);
         }
     }
+
+    #[test]
+    fn insert_persistent_preserves_receiver() {
+        let a = CloneMap::new();
+        let a = a.insert_persistent(1, "a");
+        let b = a.insert_persistent(2, "b");
+
+        assert_eq!(a.get(&1), Some(&"a"));
+        assert_eq!(a.get(&2), None);
+
+        assert_eq!(b.get(&1), Some(&"a"));
+        assert_eq!(b.get(&2), Some(&"b"));
+    }
+
+    #[test]
+    fn remove_persistent_preserves_receiver() {
+        let a = CloneMap::new();
+        let a = a.insert_persistent(1, "a");
+        let (b, val) = a.remove_persistent(&1);
+
+        assert_eq!(val, Some("a"));
+        assert_eq!(a.get(&1), Some(&"a"));
+        assert_eq!(b.get(&1), None);
+    }
+
+    #[test]
+    fn len_and_is_empty_track_mutations() {
+        let mut m = CloneMap::new();
+        assert_eq!(m.len(), 0);
+        assert!(m.is_empty());
+
+        let item_count: usize = 1 << 12;
+        for i in 0..item_count {
+            m.insert(i, i);
+        }
+        assert_eq!(m.len(), item_count);
+
+        // Overwriting a key does not change the size.
+        m.insert(0, 999);
+        assert_eq!(m.len(), item_count);
+
+        for i in 0..item_count {
+            m.remove(&i);
+        }
+        assert_eq!(m.len(), 0);
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn iter_visits_every_pair() {
+        let item_count: usize = 1 << 12;
+        let mut m = CloneMap::new();
+        for i in 0..item_count {
+            m.insert(i, i + 1);
+        }
+
+        let mut seen: Vec<usize> = m.iter().map(|(k, _)| *k).collect();
+        seen.sort();
+        assert_eq!(seen, (0..item_count).collect::<Vec<_>>());
+
+        let mut keys: Vec<usize> = m.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, (0..item_count).collect::<Vec<_>>());
+
+        let mut values: Vec<usize> = m.values().cloned().collect();
+        values.sort();
+        assert_eq!(values, (1..item_count + 1).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn into_iter_consumes_every_pair() {
+        let mut m = CloneMap::new();
+        m.insert(1, "a");
+        m.insert(2, "b");
+
+        let mut pairs: Vec<(i32, &str)> = m.into_iter().collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, "a"), (2, "b")]);
+    }
+
+    #[test]
+    fn from_iter_and_extend() {
+        let mut m: CloneMap<i32, i32> = (0..8).map(|i| (i, i * 2)).collect();
+        assert_eq!(m.len(), 8);
+        for i in 0..8 {
+            assert_eq!(m.get(&i), Some(&(i * 2)));
+        }
+
+        m.extend(vec![(8, 16), (9, 18)]);
+        assert_eq!(m.len(), 10);
+        assert_eq!(m.get(&9), Some(&18));
+    }
+
+    #[test]
+    fn remove_compacts_single_child_nodes() {
+        // Large enough to force several levels of `Branch::C` nodes.
+        let item_count: usize = 1 << 14;
+        let mut m = CloneMap::new();
+        for i in 0..item_count {
+            m.insert(i, i);
+        }
+
+        // Remove everything but one key; no interior node should be left
+        // holding only a single leaf.
+        for i in 1..item_count {
+            m.remove(&i);
+        }
+
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get(&0), Some(&0));
+        assert_eq!(m.iter().count(), 1);
+
+        // The surviving leaf has been collapsed all the way up to the root.
+        assert_eq!(m.root.branches.len(), 1);
+        assert!(match *m.root.branches[0] {
+            Branch::S(_) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn try_insert_matches_insert() {
+        let mut m = CloneMap::new();
+        assert_eq!(m.try_insert(37, "a"), Ok(None));
+        assert_eq!(m.try_insert(37, "b"), Ok(Some("a")));
+        assert_eq!(m[&37], "b");
+        assert_eq!(m.len(), 1);
+    }
+
+    #[test]
+    fn try_remove_matches_remove() {
+        let mut m = CloneMap::new();
+        m.insert(1, "a");
+        assert_eq!(m.try_remove(&1), Ok(Some("a")));
+        assert_eq!(m.try_remove(&1), Ok(None));
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn entry_or_insert_counts_without_double_traversal() {
+        let mut m: CloneMap<i32, usize> = CloneMap::new();
+
+        let item_count: usize = 1 << 14;
+        for i in 0..item_count {
+            *m.entry((i % 7) as i32).or_insert(0) += 1;
+        }
+
+        for rem in 0..7 {
+            let expected = (rem..item_count).step_by(7).count();
+            assert_eq!(m.get(&(rem as i32)), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn entry_or_insert_with_only_calls_closure_when_vacant() {
+        let mut m = CloneMap::new();
+        let mut calls = 0;
+
+        m.entry(1).or_insert_with(|| {
+            calls += 1;
+            "a"
+        });
+        m.entry(1).or_insert_with(|| {
+            calls += 1;
+            "b"
+        });
+
+        assert_eq!(calls, 1);
+        assert_eq!(m.get(&1), Some(&"a"));
+    }
+
+    #[test]
+    fn entry_or_insert_with_key_sees_the_key() {
+        let mut m: CloneMap<&str, String> = CloneMap::new();
+        m.entry("a").or_insert_with_key(|k| k.to_uppercase());
+        assert_eq!(m.get("a"), Some(&"A".to_string()));
+    }
+
+    #[test]
+    fn entry_and_modify_only_touches_occupied_entries() {
+        let mut m = CloneMap::new();
+
+        m.entry(1).and_modify(|v| *v += 1).or_insert(1);
+        assert_eq!(m.get(&1), Some(&1));
+
+        m.entry(1).and_modify(|v| *v += 1).or_insert(1);
+        assert_eq!(m.get(&1), Some(&2));
+    }
+
+    #[test]
+    fn entry_or_default_uses_the_value_types_default() {
+        let mut m: CloneMap<&str, Vec<i32>> = CloneMap::new();
+        m.entry("a").or_default().push(1);
+        m.entry("a").or_default().push(2);
+        assert_eq!(m.get("a"), Some(&vec![1, 2]));
+    }
+
+    #[test]
+    fn entry_splits_a_full_hash_collision_into_a_collision_bucket() {
+        // Force two distinct keys to share a hash by wrapping a constant.
+        #[derive(Clone, PartialEq, Eq)]
+        struct SameHash(i32);
+
+        impl ::std::hash::Hash for SameHash {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                0u64.hash(state);
+            }
+        }
+
+        let mut m = CloneMap::new();
+        *m.entry(SameHash(1)).or_insert(0) += 1;
+        *m.entry(SameHash(2)).or_insert(0) += 10;
+
+        assert_eq!(m.get(&SameHash(1)), Some(&1));
+        assert_eq!(m.get(&SameHash(2)), Some(&10));
+        assert_eq!(m.len(), 2);
+
+        assert_eq!(m.root.branches.len(), 1);
+        assert!(match *m.root.branches[0] {
+            Branch::Collision(_, ref bucket) => bucket.len() == 2,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn get_accepts_a_query_that_is_equivalent_but_not_borrowed() {
+        // `(String, u32)` has no `Borrow<Field>` impl, so this lookup is
+        // only possible by implementing `Equivalent` directly.
+        struct Field<'a>(&'a str, u32);
+
+        impl<'a> ::std::hash::Hash for Field<'a> {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                (self.0, self.1).hash(state);
+            }
+        }
+
+        impl<'a> Equivalent<(String, u32)> for Field<'a> {
+            fn equivalent(&self, key: &(String, u32)) -> bool {
+                self.0 == key.0 && self.1 == key.1
+            }
+        }
+
+        let mut m = CloneMap::new();
+        m.insert(("a".to_string(), 1), "first");
+        m.insert(("a".to_string(), 2), "second");
+
+        assert_eq!(m.get(&Field("a", 1)), Some(&"first"));
+        assert_eq!(m.get(&Field("a", 2)), Some(&"second"));
+        assert_eq!(m.get(&Field("b", 1)), None);
+    }
 }
@@ -0,0 +1,30 @@
+use std::borrow::Borrow;
+
+/// A generalization of [`Borrow`] that lets a query type compare itself
+/// against a stored key without being a strict borrowed form of it.
+///
+/// `Borrow` requires the query's [`Hash`](::std::hash::Hash) and
+/// [`Eq`](::std::cmp::Eq) impls to agree exactly with the key's (per its own
+/// documented contract), which rules out perfectly reasonable lookups like
+/// finding a `Vec<u8>` key by `&[u8]`, or a composite key by one of its
+/// borrowed fields. `Equivalent` drops that restriction: a query only needs
+/// to hash the way the key would and to know how to compare itself against
+/// one.
+///
+/// A blanket impl covers every existing `Borrow<Q>` relationship, so this is
+/// a strict generalization -- nothing that already worked via `Borrow` stops
+/// working.
+pub trait Equivalent<K: ?Sized> {
+    /// Checks if `self` is equivalent to `key`.
+    fn equivalent(&self, key: &K) -> bool;
+}
+
+impl<K, Q> Equivalent<K> for Q
+where
+    K: Borrow<Q>,
+    Q: Eq + ?Sized,
+{
+    fn equivalent(&self, key: &K) -> bool {
+        key.borrow() == self
+    }
+}
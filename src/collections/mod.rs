@@ -0,0 +1,7 @@
+pub mod clone_map;
+pub mod equivalent;
+pub mod transactional_map;
+
+pub use self::clone_map::{CloneMap, Entry, OccupiedEntry, VacantEntry};
+pub use self::equivalent::Equivalent;
+pub use self::transactional_map::{ReadTxn, TransactionalMap, WriteTxn};
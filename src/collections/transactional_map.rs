@@ -0,0 +1,299 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use collections::clone_map::CloneMap;
+
+/// One committed generation of the map: the data plus the transaction id it
+/// was published under.
+struct Generation<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    txid: u64,
+    map: CloneMap<K, V, S>,
+}
+
+/// A `CloneMap` guarded for concurrent, MVCC-style access, in the style of
+/// `concread`'s `CowCell`.
+///
+/// Readers call [`read`](TransactionalMap::read) to obtain an immutable,
+/// point-in-time [`ReadTxn`] snapshot: an `Arc` clone of whichever
+/// generation is currently published, so it is unaffected by a writer
+/// mutating its own private copy concurrently. The published root is held
+/// behind a short-lived mutex rather than a lock-free atomic pointer, so
+/// `read` briefly contends with `commit` (and with other readers) for that
+/// lock, but never blocks for the duration of a write transaction itself --
+/// only for the instant it takes to clone the current root `Arc`. A single
+/// writer at a time may call [`write`](TransactionalMap::write) to obtain a
+/// [`WriteTxn`], mutate a private copy of the map, and
+/// [`commit`](WriteTxn::commit) it to publish the new generation.
+/// Outstanding `ReadTxn`s keep their generation's root alive via `Arc`, so a
+/// commit never disturbs a read already in flight.
+pub struct TransactionalMap<K, V, S = RandomState>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    active: Mutex<Arc<Generation<K, V, S>>>,
+    writer: Mutex<()>,
+}
+
+/// An immutable, point-in-time snapshot of a [`TransactionalMap`].
+///
+/// A `ReadTxn` derefs to `CloneMap` for lookups and iteration, and reports
+/// the transaction id its generation was committed under so callers can
+/// tell whether a newer generation has since been published.
+pub struct ReadTxn<K, V, S = RandomState>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    generation: Arc<Generation<K, V, S>>,
+}
+
+/// A single, exclusive write transaction on a [`TransactionalMap`].
+///
+/// A `WriteTxn` derefs (mutably) to a private `CloneMap` that is invisible
+/// to readers until [`commit`](WriteTxn::commit) publishes it. Dropping a
+/// `WriteTxn` without committing discards the private copy, leaving the
+/// active generation untouched.
+pub struct WriteTxn<'a, K, V, S = RandomState>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    parent: &'a TransactionalMap<K, V, S>,
+    map: CloneMap<K, V, S>,
+    txid: u64,
+    _guard: MutexGuard<'a, ()>,
+}
+
+
+// Pubic API
+// --------------------------------------------------
+
+impl<K, V> TransactionalMap<K, V, RandomState>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Creates an empty `TransactionalMap` with a default branching factor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::TransactionalMap;
+    /// let map: TransactionalMap<&str, isize> = TransactionalMap::new();
+    /// ```
+    pub fn new() -> TransactionalMap<K, V> {
+        TransactionalMap::from(CloneMap::new())
+    }
+}
+
+impl<K, V, S> From<CloneMap<K, V, S>> for TransactionalMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn from(map: CloneMap<K, V, S>) -> TransactionalMap<K, V, S> {
+        TransactionalMap {
+            active: Mutex::new(Arc::new(Generation { txid: 0, map: map })),
+            writer: Mutex::new(()),
+        }
+    }
+}
+
+impl<K, V, S> TransactionalMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Returns the transaction id of the most recently committed
+    /// generation.
+    pub fn txid(&self) -> u64 {
+        self.active.lock().unwrap().txid
+    }
+
+    /// Takes an immutable, point-in-time snapshot of the map.
+    ///
+    /// This does not block on a concurrent writer's transaction: it only
+    /// takes the root mutex long enough to clone the `Arc` pointing at
+    /// whichever generation is currently published, the same brief lock a
+    /// `commit` takes to publish a new one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ripl::collections::TransactionalMap;
+    ///
+    /// let map = TransactionalMap::new();
+    /// let mut txn = map.write();
+    /// txn.insert(1, "a");
+    /// txn.commit();
+    ///
+    /// let snapshot = map.read();
+    /// assert_eq!(snapshot.get(&1), Some(&"a"));
+    /// ```
+    pub fn read(&self) -> ReadTxn<K, V, S> {
+        let generation = self.active.lock().unwrap().clone();
+        ReadTxn { generation: generation }
+    }
+
+    /// Begins a write transaction.
+    ///
+    /// Only one write transaction may be open at a time; a concurrent call
+    /// to `write` blocks until this one is committed or dropped. The
+    /// transaction mutates a private, structurally-shared copy of the map
+    /// that stays invisible to readers until `commit` publishes it.
+    pub fn write(&self) -> WriteTxn<K, V, S> {
+        let guard = self.writer.lock().unwrap();
+        let generation = self.active.lock().unwrap().clone();
+        WriteTxn {
+            parent: self,
+            map: generation.map.clone(),
+            txid: generation.txid,
+            _guard: guard,
+        }
+    }
+}
+
+impl<K, V, S> ReadTxn<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Returns the transaction id this snapshot was committed under.
+    pub fn txid(&self) -> u64 {
+        self.generation.txid
+    }
+}
+
+impl<K, V, S> Deref for ReadTxn<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    type Target = CloneMap<K, V, S>;
+    fn deref(&self) -> &CloneMap<K, V, S> {
+        &self.generation.map
+    }
+}
+
+impl<'a, K, V, S> WriteTxn<'a, K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Publishes this transaction's private copy as the new active
+    /// generation, returning its transaction id.
+    ///
+    /// `ReadTxn`s obtained before this call keep observing the old
+    /// generation: their root `Arc` keeps it alive.
+    pub fn commit(self) -> u64 {
+        let txid = self.txid + 1;
+        let generation = Arc::new(Generation { txid: txid, map: self.map });
+        *self.parent.active.lock().unwrap() = generation;
+        txid
+    }
+}
+
+impl<'a, K, V, S> Deref for WriteTxn<'a, K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    type Target = CloneMap<K, V, S>;
+    fn deref(&self) -> &CloneMap<K, V, S> {
+        &self.map
+    }
+}
+
+impl<'a, K, V, S> DerefMut for WriteTxn<'a, K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn deref_mut(&mut self) -> &mut CloneMap<K, V, S> {
+        &mut self.map
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_sees_committed_writes() {
+        let map = TransactionalMap::new();
+
+        let mut txn = map.write();
+        txn.insert(1, "a");
+        txn.commit();
+
+        let snapshot = map.read();
+        assert_eq!(snapshot.get(&1), Some(&"a"));
+    }
+
+    #[test]
+    fn reader_snapshot_is_isolated_from_later_writes() {
+        let map = TransactionalMap::new();
+
+        let mut txn = map.write();
+        txn.insert(1, "a");
+        txn.commit();
+
+        let before = map.read();
+
+        let mut txn = map.write();
+        txn.insert(1, "b");
+        txn.commit();
+
+        assert_eq!(before.get(&1), Some(&"a"));
+        assert_eq!(map.read().get(&1), Some(&"b"));
+    }
+
+    #[test]
+    fn dropping_a_write_txn_without_committing_is_a_no_op() {
+        let map = TransactionalMap::new();
+
+        {
+            let mut txn = map.write();
+            txn.insert(1, "a");
+            // `txn` is dropped here without calling `commit`.
+        }
+
+        assert_eq!(map.read().get(&1), None);
+    }
+
+    #[test]
+    fn txid_increases_monotonically_on_commit() {
+        let map = TransactionalMap::new();
+        assert_eq!(map.txid(), 0);
+
+        let mut txn = map.write();
+        txn.insert(1, "a");
+        assert_eq!(txn.commit(), 1);
+        assert_eq!(map.read().txid(), 1);
+
+        let mut txn = map.write();
+        txn.insert(2, "b");
+        assert_eq!(txn.commit(), 2);
+        assert_eq!(map.read().txid(), 2);
+        assert_eq!(map.txid(), 2);
+    }
+}
@@ -1,10 +1,13 @@
 use std::collections::HashMap;
+use std::slice;
 use std::sync::Arc;
+use std::vec;
 
+use syntax::namespace::Name;
 use syntax::{Structure, Symbol};
 
 pub struct DataBase<'ns> {
-    preds: HashMap<Symbol<'ns>, Vec<Rule<'ns>>>,
+    preds: HashMap<(Name<'ns>, u32), Predicate<'ns>>,
 }
 
 #[derive(Clone)]
@@ -13,26 +16,257 @@ pub struct Rule<'ns> {
     body: Option<Arc<Structure<'ns>>>,
 }
 
+/// A predicate's clauses, plus a first-argument index over them.
+///
+/// `index` maps the principal functor/constant of a clause head's first
+/// argument to the (ascending, assertion-order) positions of the matching
+/// clauses in `rules`. `vars` holds the positions of clauses whose first
+/// argument is a variable (or which are 0-ary), since those can unify with
+/// any goal regardless of what the goal's first argument is.
+struct Predicate<'ns> {
+    rules: Vec<Rule<'ns>>,
+    index: HashMap<IndexKey<'ns>, Vec<usize>>,
+    vars: Vec<usize>,
+}
+
+/// The principal functor or constant of a term, used to key the first-
+/// argument index.
+///
+/// Compound terms are keyed by functor and arity; atoms, numbers, and
+/// strings are keyed by value. `Symbol::List` cells have their own `Nil`
+/// and `Cons` keys, distinct from the keys of a `'[]'` atom and a `'.'/2`
+/// compound.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum IndexKey<'ns> {
+    Atom(Name<'ns>),
+    Int(i64),
+    Float(u64),
+    Str(&'ns str),
+    Compound(Name<'ns>, u32),
+    Nil,
+    Cons,
+}
+
+impl<'ns> IndexKey<'ns> {
+    /// Returns the index key for a symbol, or `None` for a variable, which
+    /// belongs in the catch-all bucket instead of any single key's bucket.
+    fn of(sym: Symbol<'ns>) -> Option<IndexKey<'ns>> {
+        match sym {
+            Symbol::Funct(0, name) => Some(IndexKey::Atom(name)),
+            Symbol::Funct(arity, name) => Some(IndexKey::Compound(name, arity)),
+            Symbol::Int(val) => Some(IndexKey::Int(val)),
+            Symbol::Float(val) => Some(IndexKey::Float(val.to_bits())),
+            Symbol::Str(val) => Some(IndexKey::Str(val)),
+            Symbol::List(true, 0) => Some(IndexKey::Nil),
+            Symbol::List(..) => Some(IndexKey::Cons),
+            Symbol::Var(_) => None,
+        }
+    }
+}
+
 impl<'ns> DataBase<'ns> {
     pub fn new() -> DataBase<'ns> {
         DataBase { preds: HashMap::new() }
     }
 
     pub fn assert(&mut self, head: Arc<Structure<'ns>>, body: Option<Arc<Structure<'ns>>>) {
-        let functor = head.functor();
-        let rules = self.preds.entry(functor).or_insert(vec![]);
-        rules.push(Rule::new(head, body));
+        let key = functor_key(&head);
+        let pred = self.preds.entry(key).or_insert_with(Predicate::new);
+        pred.insert(Rule::new(head, body));
     }
 
+    /// Returns the clauses that could unify with `head`, in assertion order.
+    ///
+    /// When `head`'s first argument is bound to a constant or compound term,
+    /// only clauses whose own first argument might unify with it are
+    /// returned (the matching bucket of the predicate's first-argument
+    /// index, plus clauses whose first argument is a variable), instead of
+    /// cloning every clause of the predicate.
     pub fn query(&self, head: Arc<Structure<'ns>>) -> Vec<Rule<'ns>> {
-        let functor = head.functor();
-        match self.preds.get(&functor) {
-            Some(rules) => rules.clone(),
-            None => vec![],
+        self.query_iter(head).collect()
+    }
+
+    /// Returns an iterator over the clauses that could unify with `head`, in
+    /// assertion order.
+    ///
+    /// Unlike `query`, this does not eagerly clone the whole candidate
+    /// `Vec`: each `Rule` is only cloned (a cheap `Arc` bump) as it's pulled
+    /// from the iterator, so a solver can stop as soon as it finds a
+    /// solution.
+    pub fn query_iter<'a>(&'a self, head: Arc<Structure<'ns>>) -> QueryIter<'a, 'ns> {
+        match self.preds.get(&functor_key(&head)) {
+            Some(pred) => pred.candidate_iter(&head),
+            None => QueryIter::All([].iter()),
+        }
+    }
+
+    /// Removes the first clause whose head and body are structurally equal
+    /// to the given ones. Returns whether a clause was removed.
+    pub fn retract(&mut self, head: &Structure<'ns>, body: Option<&Structure<'ns>>) -> bool {
+        match self.preds.get_mut(&functor_key(head)) {
+            Some(pred) => pred.retract(head, body),
+            None => false,
+        }
+    }
+
+    /// Removes every clause whose head and body are structurally equal to
+    /// the given ones. Returns the number of clauses removed.
+    pub fn retract_all(&mut self, head: &Structure<'ns>, body: Option<&Structure<'ns>>) -> usize {
+        match self.preds.get_mut(&functor_key(head)) {
+            Some(pred) => pred.retract_all(head, body),
+            None => 0,
         }
     }
+
+    /// Removes every clause of the predicate with the given name and arity.
+    pub fn abolish(&mut self, name: Name<'ns>, arity: u32) {
+        self.preds.remove(&(name, arity));
+    }
+}
+
+/// A lazy iterator over a predicate's candidate clauses.
+///
+/// Yielded by [`DataBase::query_iter`]. `All` walks every clause of the
+/// predicate (used when the goal's first argument can't narrow the
+/// candidates, e.g. a 0-ary goal); `Indexed` walks a precomputed list of
+/// clause positions drawn from the first-argument index.
+///
+/// [`DataBase::query_iter`]: ./struct.DataBase.html#method.query_iter
+pub enum QueryIter<'a, 'ns: 'a> {
+    All(slice::Iter<'a, Rule<'ns>>),
+    Indexed {
+        idxs: vec::IntoIter<usize>,
+        rules: &'a [Rule<'ns>],
+    },
 }
 
+impl<'a, 'ns> Iterator for QueryIter<'a, 'ns> {
+    type Item = Rule<'ns>;
+
+    fn next(&mut self) -> Option<Rule<'ns>> {
+        match *self {
+            QueryIter::All(ref mut it) => it.next().cloned(),
+            QueryIter::Indexed { ref mut idxs, rules } => idxs.next().map(|i| rules[i].clone()),
+        }
+    }
+}
+
+/// Returns the `(name, arity)` under which a clause head or goal is filed.
+fn functor_key<'ns>(head: &Structure<'ns>) -> (Name<'ns>, u32) {
+    match head.functor() {
+        Symbol::Funct(arity, name) => (name, arity),
+        other => panic!("a clause head or goal must be a functor, got {:?}", other),
+    }
+}
+
+impl<'ns> Predicate<'ns> {
+    fn new() -> Predicate<'ns> {
+        Predicate {
+            rules: Vec::new(),
+            index: HashMap::new(),
+            vars: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, rule: Rule<'ns>) {
+        let i = self.rules.len();
+        match first_arg(&rule.head).and_then(IndexKey::of) {
+            Some(key) => self.index.entry(key).or_insert_with(Vec::new).push(i),
+            None => self.vars.push(i),
+        }
+        self.rules.push(rule);
+    }
+
+    fn candidate_iter<'a>(&'a self, goal: &Structure<'ns>) -> QueryIter<'a, 'ns> {
+        let key = match first_arg(goal) {
+            Some(sym) => IndexKey::of(sym),
+            None => None,
+        };
+
+        let mut idxs = match key {
+            None => return QueryIter::All(self.rules.iter()),
+            Some(key) => self.index.get(&key).cloned().unwrap_or_else(Vec::new),
+        };
+        idxs.extend(self.vars.iter().cloned());
+        idxs.sort();
+        QueryIter::Indexed {
+            idxs: idxs.into_iter(),
+            rules: &self.rules,
+        }
+    }
+
+    /// Removes the first clause whose head and body are structurally equal
+    /// to the given ones. Returns whether a clause was removed.
+    fn retract(&mut self, head: &Structure<'ns>, body: Option<&Structure<'ns>>) -> bool {
+        let pos = self.rules.iter().position(|rule| rule.matches(head, body));
+        match pos {
+            Some(i) => {
+                self.rules.remove(i);
+                self.reindex();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes every clause whose head and body are structurally equal to
+    /// the given ones. Returns the number of clauses removed.
+    fn retract_all(&mut self, head: &Structure<'ns>, body: Option<&Structure<'ns>>) -> usize {
+        let before = self.rules.len();
+        self.rules.retain(|rule| !rule.matches(head, body));
+        self.reindex();
+        before - self.rules.len()
+    }
+
+    /// Rebuilds the first-argument index from `self.rules`.
+    ///
+    /// A retraction shifts every later clause's position, which would
+    /// otherwise leave the index pointing at the wrong clauses (or past the
+    /// end of `self.rules` entirely).
+    fn reindex(&mut self) {
+        self.index.clear();
+        self.vars.clear();
+        for (i, rule) in self.rules.iter().enumerate() {
+            match first_arg(&rule.head).and_then(IndexKey::of) {
+                Some(key) => self.index.entry(key).or_insert_with(Vec::new).push(i),
+                None => self.vars.push(i),
+            }
+        }
+    }
+}
+
+/// Returns the principal symbol of `head`'s first (leftmost) argument, or
+/// `None` if `head` is 0-ary.
+///
+/// A `Structure` stores its symbols in postfix order, so a term's children
+/// appear left-to-right but *end* right-to-left relative to their parent's
+/// functor. Finding the first argument means walking back through the
+/// functor's children, from the rightmost to the leftmost, one subtree at a
+/// time.
+fn first_arg<'ns>(head: &Structure<'ns>) -> Option<Symbol<'ns>> {
+    let slice = head.as_slice();
+    let arity = head.arity();
+    if arity == 0 {
+        return None;
+    }
+
+    let mut end = slice.len() - 1;
+    let mut start = subtree_start(slice, end);
+    for _ in 1..arity {
+        end = start;
+        start = subtree_start(slice, end);
+    }
+    Some(slice[end - 1])
+}
+
+/// Returns the start index of the subtree whose root sits at `slice[end - 1]`.
+fn subtree_start<'ns>(slice: &[Symbol<'ns>], end: usize) -> usize {
+    let mut pos = end - 1;
+    for _ in 0..slice[pos].arity() {
+        pos = subtree_start(slice, pos);
+    }
+    pos
+}
 
 impl<'ns> Rule<'ns> {
     fn new(head: Arc<Structure<'ns>>, body: Option<Arc<Structure<'ns>>>) -> Rule<'ns> {
@@ -41,4 +275,124 @@ impl<'ns> Rule<'ns> {
             body: body,
         }
     }
+
+    /// Returns whether this rule's head and body are structurally equal to
+    /// the given ones.
+    fn matches(&self, head: &Structure<'ns>, body: Option<&Structure<'ns>>) -> bool {
+        *self.head == *head && self.body.as_ref().map(|b| &**b) == body
+    }
+}
+
+// Tests
+// --------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use syntax::namespace::NameSpace;
+    use syntax::test_util::struct_from_vec;
+    use syntax::Symbol::*;
+
+    /// Builds a clause head `name(args...)` (or the 0-ary atom `name` when
+    /// `args` is empty), where each element of `args` is the postorder
+    /// encoding of one whole top-level argument (so a compound argument like
+    /// `f(1)` is its own `vec![Int(1), Funct(1, f)]`, not flattened in with
+    /// its siblings) -- `name`'s arity is the number of argument groups, not
+    /// the total symbol count.
+    fn fact<'ns>(args: Vec<Vec<Symbol<'ns>>>, name: Name<'ns>) -> Arc<Structure<'ns>> {
+        let arity = args.len() as u32;
+        let mut buf: Vec<Symbol<'ns>> = args.into_iter().flat_map(|arg| arg).collect();
+        buf.push(Symbol::Funct(arity, name));
+        Arc::from(unsafe { struct_from_vec(buf) })
+    }
+
+    #[test]
+    fn indexes_on_a_compound_first_argument() {
+        let ns = NameSpace::new();
+        let mut db = DataBase::new();
+
+        let p = ns.name("p");
+        let f = ns.name("f");
+
+        let fact_a = fact(vec![vec![Funct(0, ns.name("a"))]], p);
+        let fact_b = fact(vec![vec![Int(1), Funct(1, f)]], p);
+        let fact_c = fact(vec![vec![Int(2), Funct(1, f)]], p);
+        let fact_d = fact(vec![vec![Var(0)]], p);
+
+        db.assert(fact_a.clone(), None);
+        db.assert(fact_b.clone(), None);
+        db.assert(fact_c.clone(), None);
+        db.assert(fact_d.clone(), None);
+
+        // A goal whose first argument is a compound `f/1` should only match
+        // the `f/1`-headed clauses and the variable-headed one, not `a`, and
+        // should yield them in assertion order.
+        let goal = fact(vec![vec![Int(99), Funct(1, f)]], p);
+        let results: Vec<_> = db.query(goal).into_iter().map(|r| r.head).collect();
+        assert_eq!(results, vec![fact_b, fact_c, fact_d]);
+    }
+
+    #[test]
+    fn retract_reindexes_the_remaining_clauses() {
+        let ns = NameSpace::new();
+        let mut db = DataBase::new();
+
+        let p = ns.name("p");
+        let a = ns.name("a");
+        let b = ns.name("b");
+
+        let first = fact(vec![vec![Funct(0, a)]], p);
+        let second = fact(vec![vec![Funct(0, b)]], p);
+        let third = fact(vec![vec![Funct(0, b)]], p);
+
+        db.assert(first.clone(), None);
+        db.assert(second.clone(), None);
+        db.assert(third.clone(), None);
+
+        // Retracting the middle clause shifts `third` from position 2 down
+        // to position 1. Without a reindex, the `b` bucket would still
+        // contain the stale position 2.
+        assert!(db.retract(&second, None));
+
+        let goal = fact(vec![vec![Funct(0, b)]], p);
+        let results: Vec<_> = db.query(goal).into_iter().map(|r| r.head).collect();
+        assert_eq!(results, vec![third]);
+
+        let goal = fact(vec![vec![Funct(0, a)]], p);
+        let results: Vec<_> = db.query(goal).into_iter().map(|r| r.head).collect();
+        assert_eq!(results, vec![first]);
+    }
+
+    #[test]
+    fn retract_all_removes_every_matching_clause() {
+        let ns = NameSpace::new();
+        let mut db = DataBase::new();
+
+        let p = ns.name("p");
+        let a = ns.name("a");
+        let b = ns.name("b");
+
+        db.assert(fact(vec![vec![Funct(0, a)]], p), None);
+        db.assert(fact(vec![vec![Funct(0, b)]], p), None);
+        db.assert(fact(vec![vec![Funct(0, a)]], p), None);
+
+        let target = fact(vec![vec![Funct(0, a)]], p);
+        assert_eq!(db.retract_all(&target, None), 2);
+
+        let goal = fact(vec![vec![Funct(0, b)]], p);
+        let results: Vec<_> = db.query(goal).into_iter().map(|r| r.head).collect();
+        assert_eq!(results, vec![fact(vec![vec![Funct(0, b)]], p)]);
+    }
+
+    #[test]
+    fn zero_arity_clause_lands_in_vars() {
+        let ns = NameSpace::new();
+        let ready = fact(vec![], ns.name("ready"));
+
+        let mut pred = Predicate::new();
+        pred.insert(Rule::new(ready, None));
+
+        assert_eq!(pred.vars, vec![0]);
+        assert!(pred.index.is_empty());
+    }
 }
@@ -1,3 +1,7 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+
 use ::namespace::NameSpace;
 
 fn is_special(ch: char) -> bool {
@@ -8,89 +12,251 @@ fn is_symbolic(ch: char) -> bool {
     !ch.is_alphanumeric() && !ch.is_whitespace() && !ch.is_control() && !is_special(ch)
 }
 
+/// Parses a run of digits (as already validated by the caller) into a `BigInt`, used as the
+/// fallback when a literal's digits don't fit an `i64`.
+fn parse_big_int(digits: &str, radix: u32) -> Option<BigInt> {
+    BigInt::parse_bytes(digits.as_bytes(), radix)
+}
+
+/// A 1-indexed line and column into the lexed source.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Loc {
+    pub line: u32,
+    pub col: u32,
+}
+
+impl Loc {
+    /// Advances past a newline: moves to the next line and resets to column 1.
+    pub fn bump_line(&mut self) {
+        self.line += 1;
+        self.col = 1;
+    }
+
+    /// Advances past a single, one-column-wide character.
+    pub fn bump_col(&mut self) {
+        self.col += 1;
+    }
+}
+
+/// Where a token starts and how much of the source it covers.
+///
+/// `start` gives the 1-based line/column a token begins at, `offset` gives the same starting
+/// point as an absolute byte offset into the source, and `len` gives the token's length in
+/// bytes. Together, `offset` and `len` are stable under `str` slicing, so a token's exact source
+/// text can be recovered (for error underlining, editor tooling, or re-emitting the original
+/// text) without re-deriving it from line/column counters.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Span {
+    pub start: Loc,
+    pub len: u32,
+    pub offset: usize,
+}
+
+impl Span {
+    /// The old `(line, col)` pair, for callers migrating off of it incrementally.
+    pub fn line_col(&self) -> (u32, u32) {
+        (self.start.line, self.start.col)
+    }
+}
+
+/// A value paired with the span of source text it was lexed from.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+/// The kind of lexical error encountered while scanning a token.
+///
+/// This is a plain, matchable code so that downstream parsers can render their own diagnostics
+/// instead of depending on the wording of [`LexError`]'s `Display` impl.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ErrorKind {
+    /// An integer literal's digits could not be parsed, even as a `BigInt`.
+    NumberOutOfRange,
+    /// A float literal had a malformed mantissa or exponent.
+    MalformedFloat,
+    /// A quoted atom or string ended before its closing quote.
+    UnclosedQuote,
+    /// A `\` escape in a quoted token was neither a known single-char escape nor a well-formed
+    /// octal/hex escape, or its code point was out of Unicode range.
+    InvalidEscape,
+    /// A `/* ... */` block comment ended before its matching close, accounting for nesting.
+    UnclosedComment,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::NumberOutOfRange => write!(f, "cannot parse number"),
+            ErrorKind::MalformedFloat => write!(f, "cannot parse number"),
+            ErrorKind::UnclosedQuote => write!(f, "unclosed quote"),
+            ErrorKind::InvalidEscape => write!(f, "invalid escape sequence"),
+            ErrorKind::UnclosedComment => write!(f, "unclosed comment"),
+        }
+    }
+}
+
+/// A lexical error, giving the kind of failure and where it occurred.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct LexError {
+    pub loc: Loc,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+/// A lexical item of Prolog with no dependency on a symbol table.
+///
+/// `RawToken` carries only a variant's payload; its position is given separately by the
+/// [`Span`] of the [`Spanned<RawToken>`][Spanned] a [`RawLexer`] yields. `Funct`, `Str`, and
+/// `Var` tokens carry their recovered source text directly rather than an interned symbol, so a
+/// `RawLexer` can run with no arena at all -- useful for tools like formatters or syntax
+/// highlighters that have no interner and don't want one.
+///
+/// Lexical errors are given as a `RawToken::Err` carrying a [`LexError`].
+#[derive(PartialEq, Debug, Clone)]
+pub enum RawToken {
+    Err(LexError),
+    Funct(String),
+    Str(String),
+    Var(String),
+    Int(i64),
+    /// An integer literal whose digits don't fit an `i64`, carried as an arbitrary-precision
+    /// `BigInt` instead of being reported as `RawToken::Err(NumberOutOfRange)`.
+    BigInt(BigInt),
+    Float(f64),
+    ParenOpen,
+    ParenClose,
+    BracketOpen,
+    BracketClose,
+    BraceOpen,
+    BraceClose,
+    Bar,
+    Comma,
+    Dot,
+    Comment(String),
+}
+
 /// A lexical item of Prolog.
 ///
-/// Every `Token` includes its line and column as the first two members. When relevant, the third
+/// Like [`RawToken`], `Token` carries only a variant's payload; its position comes from the
+/// [`Span`] of the [`Spanned<Token>`][Spanned] a [`Lexer`] yields. When relevant, a variant's
 /// member gives the interned value of the token.
 ///
-/// Lexical errors are given as a `Token::Err` whose value is the error message.
-#[derive(PartialEq, Debug, Clone, Copy)]
+/// Lexical errors are given as a `Token::Err` carrying a [`LexError`].
+#[derive(PartialEq, Debug, Clone)]
 pub enum Token {
-    Err(u32, u32, &'static str), // TODO: Change error from str to an error code
-    Funct(u32, u32, usize),
-    Str(u32, u32, usize),
-    Var(u32, u32, usize),
-    Int(u32, u32, i64),
-    Float(u32, u32, f64),
-    ParenOpen(u32, u32),
-    ParenClose(u32, u32),
-    BracketOpen(u32, u32),
-    BracketClose(u32, u32),
-    BraceOpen(u32, u32),
-    BraceClose(u32, u32),
-    Bar(u32, u32),
-    Comma(u32, u32),
-    Dot(u32, u32),
+    Err(LexError),
+    Funct(usize),
+    Str(usize),
+    Var(usize),
+    Int(i64),
+    BigInt(BigInt),
+    Float(f64),
+    ParenOpen,
+    ParenClose,
+    BracketOpen,
+    BracketClose,
+    BraceOpen,
+    BraceClose,
+    Bar,
+    Comma,
+    Dot,
+    /// A `%` line comment or a `/* ... */` block comment, returned only when the lexer is constructed to keep
+    /// comments (see [`RawLexer::new_keep_comments`] / [`Lexer::new_keep_comments`]).
+    Comment(usize),
 }
 
-/// An iterator over `Token`s.
-pub struct Lexer<'ns, I> {
+/// An iterator over spanned `RawToken`s.
+///
+/// `RawLexer` does the actual scanning. It has no notion of a symbol table: `Funct`, `Str`, and
+/// `Var` tokens carry their text as an owned `String`. [`Lexer`] wraps a `RawLexer` and interns
+/// that text, so most callers should reach for `Lexer` instead -- this type is for callers who
+/// want tokens without paying for (or requiring) a `NameSpace`.
+///
+/// This mirrors the usual split between "pure" lexing and interning: `RawLexer` can run in a
+/// tool (a formatter, a syntax highlighter, an error reporter) that has no `NameSpace` at hand
+/// and no need to pay interning's cost, while `Lexer` stays the everyday entry point for callers
+/// that do.
+pub struct RawLexer<I> {
     inner: I,
-    ns: &'ns NameSpace,
     buf: String,
-    line: u32,
-    col: u32,
+    loc: Loc,
+    pos: usize,
+    start: usize,
+    keep_comments: bool,
 }
 
-impl<'ns, I> Lexer<'ns, I>
+impl<I> RawLexer<I>
     where I: Iterator<Item = char>
 {
-    pub fn new(chars: I, ns: &'ns NameSpace) -> Lexer<'ns, I> {
-        Lexer {
+    pub fn new(chars: I) -> RawLexer<I> {
+        RawLexer {
             inner: chars,
-            ns: ns,
             buf: String::with_capacity(32),
-            line: 1,
-            col: 1,
+            loc: Loc { line: 1, col: 1 },
+            pos: 0,
+            start: 0,
+            keep_comments: false,
         }
     }
+
+    /// Like [`RawLexer::new`], but comments are yielded as `RawToken::Comment` tokens instead of
+    /// being discarded.
+    pub fn new_keep_comments(chars: I) -> RawLexer<I> {
+        RawLexer { keep_comments: true, ..RawLexer::new(chars) }
+    }
 }
 
-/// The Iterator implemntation for Lexer.
+/// The Iterator implemntation for RawLexer.
 ///
 /// TODO: Upgrade to FusedIterator once that stabilizes.
 /// https://doc.rust-lang.org/std/iter/trait.FusedIterator.html
-impl<'ns, I> Iterator for Lexer<'ns, I>
+impl<I> Iterator for RawLexer<I>
     where I: Iterator<Item = char>
 {
-    type Item = Token;
-    fn next(&mut self) -> Option<Token> {
+    type Item = Spanned<RawToken>;
+    fn next(&mut self) -> Option<Spanned<RawToken>> {
         let next = match self.buf.pop() {
             Some(ch) => Some(ch),
-            None => self.inner.next(),
+            None => self.bump(),
         };
-        match next {
-            Some('(') => self.lex_simple('('),
-            Some(')') => self.lex_simple(')'),
-            Some('[') => self.lex_simple('['),
-            Some(']') => self.lex_simple(']'),
-            Some('{') => self.lex_simple('{'),
-            Some('}') => self.lex_simple('}'),
-            Some(',') => self.lex_simple(','),
-            Some('|') => self.lex_simple('|'),
-            Some('.') => self.lex_simple('.'),
-            Some('%') => self.lex_comment(),
-            Some('_') => self.lex_var('_'),
-            Some('\'') => self.lex_quote('\''),
-            Some('\"') => self.lex_quote('\"'),
-            Some('-') => self.lex_minus(),
-            Some('0') => self.lex_zero(),
-            Some(ch) if ch.is_digit(10) => self.lex_decimal(ch),
-            Some(ch) if ch.is_whitespace() => self.lex_space(ch),
-            Some(ch) if ch.is_control() => self.lex_space(ch),
-            Some(ch) if ch.is_uppercase() => self.lex_var(ch),
-            Some(ch) => self.lex_functor(ch),
-            None => None,
+        let ch = match next {
+            Some(ch) => ch,
+            None => return None,
+        };
+
+        // `ch` has already been bumped (whether freshly read or reused from `buf`), so `self.pos`
+        // sits just past it; back up by its width to find where this token begins.
+        self.start = self.pos - ch.len_utf8();
+
+        match ch {
+            '(' => self.lex_simple('('),
+            ')' => self.lex_simple(')'),
+            '[' => self.lex_simple('['),
+            ']' => self.lex_simple(']'),
+            '{' => self.lex_simple('{'),
+            '}' => self.lex_simple('}'),
+            ',' => self.lex_simple(','),
+            '|' => self.lex_simple('|'),
+            '.' => self.lex_simple('.'),
+            '%' => self.lex_comment(),
+            '/' => self.lex_slash(),
+            '_' => self.lex_var('_'),
+            '\'' => self.lex_quote('\''),
+            '\"' => self.lex_quote('\"'),
+            '-' => self.lex_minus(),
+            '0' => self.lex_zero(),
+            ch if ch.is_digit(10) => self.lex_decimal(ch),
+            ch if ch.is_whitespace() => self.lex_space(ch),
+            ch if ch.is_control() => self.lex_space(ch),
+            ch if ch.is_uppercase() => self.lex_var(ch),
+            ch => self.lex_functor(ch),
         }
     }
 }
@@ -101,25 +267,37 @@ impl<'ns, I> Iterator for Lexer<'ns, I>
 /// argument. These functions must clear the buffer before returning. They may read one character
 /// beyond the token they are lexing. In that case, they must put the extra character onto the
 /// buffer before returning.
-impl<'ns, I> Lexer<'ns, I>
+impl<I> RawLexer<I>
     where I: Iterator<Item = char>
 {
-    /// Returns the interned symbol for the token.
-    fn get_symbol(&mut self) -> usize {
-        self.ns.intern(self.buf.as_str())
+    /// Reads the next char from the underlying iterator, advancing `self.pos` past it.
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.inner.next();
+        if let Some(ch) = ch {
+            self.pos += ch.len_utf8();
+        }
+        ch
+    }
+
+    /// Returns the buffered text, clearing the buffer.
+    fn take_text(&mut self) -> String {
+        let text = self.buf.clone();
+        self.buf.clear();
+        text
     }
 
     /// Returns the token for a simple function symbol.
-    fn lex_functor(&mut self, first: char) -> Option<Token> {
+    fn lex_functor(&mut self, first: char) -> Option<Spanned<RawToken>> {
         if is_symbolic(first) {
             return self.lex_symbolic(first);
         }
 
-        let line = self.line;
-        let col = self.col;
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
         self.buf.push(first); // assume first char is valid
         loop {
-            match self.inner.next() {
+            match self.bump() {
                 Some('_') => {
                     self.buf.push('_');
                 }
@@ -127,29 +305,30 @@ impl<'ns, I> Lexer<'ns, I>
                     self.buf.push(ch);
                 }
                 Some(ch) => {
-                    let tok = Token::Funct(line, col, self.get_symbol());
-                    self.col += self.buf.len() as u32;
-                    self.buf.clear();
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos - ch.len_utf8();
+                    let value = RawToken::Funct(self.take_text());
                     self.buf.push(ch);
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
                 None => {
-                    let tok = Token::Funct(line, col, self.get_symbol());
-                    self.col += self.buf.len() as u32;
-                    self.buf.clear();
-                    return Some(tok);
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos;
+                    let value = RawToken::Funct(self.take_text());
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
             }
         }
     }
 
     /// Returns the token for a simple function symbol starting with a symbolic char.
-    fn lex_symbolic(&mut self, first: char) -> Option<Token> {
-        let line = self.line;
-        let col = self.col;
+    fn lex_symbolic(&mut self, first: char) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
         self.buf.push(first); // assume first char is valid
         loop {
-            match self.inner.next() {
+            match self.bump() {
                 Some('_') => {
                     self.buf.push('_');
                 }
@@ -157,29 +336,30 @@ impl<'ns, I> Lexer<'ns, I>
                     self.buf.push(ch);
                 }
                 Some(ch) => {
-                    let tok = Token::Funct(line, col, self.get_symbol());
-                    self.col += self.buf.len() as u32;
-                    self.buf.clear();
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos - ch.len_utf8();
+                    let value = RawToken::Funct(self.take_text());
                     self.buf.push(ch);
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
                 None => {
-                    let tok = Token::Funct(line, col, self.get_symbol());
-                    self.col += self.buf.len() as u32;
-                    self.buf.clear();
-                    return Some(tok);
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos;
+                    let value = RawToken::Funct(self.take_text());
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
             }
         }
     }
 
     /// Returns the token for a variable term.
-    fn lex_var(&mut self, first: char) -> Option<Token> {
-        let line = self.line;
-        let col = self.col;
+    fn lex_var(&mut self, first: char) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
         self.buf.push(first); // assume first char is valid
         loop {
-            match self.inner.next() {
+            match self.bump() {
                 Some('_') => {
                     self.buf.push('_');
                 }
@@ -187,293 +367,565 @@ impl<'ns, I> Lexer<'ns, I>
                     self.buf.push(ch);
                 }
                 Some(ch) => {
-                    let tok = Token::Var(line, col, self.get_symbol());
-                    self.col += self.buf.len() as u32;
-                    self.buf.clear();
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos - ch.len_utf8();
+                    let value = RawToken::Var(self.take_text());
                     self.buf.push(ch);
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
                 None => {
-                    let tok = Token::Var(line, col, self.get_symbol());
-                    self.col += self.buf.len() as u32;
-                    self.buf.clear();
-                    return Some(tok);
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos;
+                    let value = RawToken::Var(self.take_text());
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
             }
         }
     }
 
     /// Returns the token for a symbol starting with a minus.
-    fn lex_minus(&mut self) -> Option<Token> {
-        let line = self.line;
-        let col = self.col;
+    fn lex_minus(&mut self) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
         self.buf.push('-');
-        match self.inner.next() {
+        match self.bump() {
             Some('0') => self.lex_zero(),
             Some(ch) if ch.is_digit(10) => self.lex_decimal(ch),
             Some(ch) if is_symbolic(ch) => self.lex_functor(ch),
             Some(ch) => {
-                let tok = Token::Funct(line, col, self.get_symbol());
-                self.col += self.buf.len() as u32;
-                self.buf.clear();
+                self.loc.col += self.buf.len() as u32;
+                let end = self.pos - ch.len_utf8();
+                let value = RawToken::Funct(self.take_text());
                 self.buf.push(ch);
-                Some(tok)
+                Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } })
             }
             None => {
-                let tok = Token::Funct(line, col, self.get_symbol());
-                self.col += self.buf.len() as u32;
-                self.buf.clear();
-                Some(tok)
+                self.loc.col += self.buf.len() as u32;
+                let end = self.pos;
+                let value = RawToken::Funct(self.take_text());
+                Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } })
             }
         }
     }
 
     /// Returns the token for a binary, octal, hexidecimal, or decimal number.
-    fn lex_zero(&mut self) -> Option<Token> {
-        let line = self.line;
-        let col = self.col;
+    fn lex_zero(&mut self) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
         let radix: u32;
         self.buf.push('0');
-        match self.inner.next() {
+        match self.bump() {
             Some('x') => radix = 16,
             Some('o') => radix = 8,
             Some('b') => radix = 2,
             Some('.') => return self.lex_decimal('.'),
             Some(ch) if ch.is_digit(10) => return self.lex_decimal(ch),
+            Some('\'') => return self.lex_char_code(line, col, start),
             Some(ch) => {
-                self.col += 1;
+                self.loc.bump_col();
+                let end = self.pos - ch.len_utf8();
                 self.buf.push(ch);
-                return Some(Token::Int(line, col, 0));
+                return Some(Spanned { value: RawToken::Int(0), span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
             }
             None => {
-                self.col += 1;
-                return Some(Token::Int(line, col, 0));
+                self.loc.bump_col();
+                let end = self.pos;
+                return Some(Spanned { value: RawToken::Int(0), span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
             }
         }
 
         // we don't add the radix char ('x', 'o', or 'b') to the buffer,
         // but we still need to adjust the column count.
-        self.col += 1;
+        self.loc.bump_col();
 
         loop {
-            match self.inner.next() {
+            match self.bump() {
                 Some(ch) if ch.is_digit(radix) => self.buf.push(ch),
                 Some(ch) => {
-                    let tok = match i64::from_str_radix(self.buf.as_str(), radix) {
-                        Ok(x) => Token::Int(line, col, x),
-                        Err(_) => Token::Err(line, col, "cannot parse number"),
+                    let value = match i64::from_str_radix(self.buf.as_str(), radix) {
+                        Ok(x) => RawToken::Int(x),
+                        Err(_) => match parse_big_int(self.buf.as_str(), radix) {
+                            Some(n) => RawToken::BigInt(n),
+                            None => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::NumberOutOfRange }),
+                        },
                     };
-                    self.col += self.buf.len() as u32;
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos - ch.len_utf8();
                     self.buf.clear();
                     self.buf.push(ch);
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
                 None => {
-                    let tok = match i64::from_str_radix(self.buf.as_str(), radix) {
-                        Ok(x) => Token::Int(line, col, x),
-                        Err(_) => Token::Err(line, col, "cannot parse number"),
+                    let value = match i64::from_str_radix(self.buf.as_str(), radix) {
+                        Ok(x) => RawToken::Int(x),
+                        Err(_) => match parse_big_int(self.buf.as_str(), radix) {
+                            Some(n) => RawToken::BigInt(n),
+                            None => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::NumberOutOfRange }),
+                        },
                     };
-                    self.col += self.buf.len() as u32;
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos;
                     self.buf.clear();
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
+                }
+            }
+        }
+    }
+
+    /// Returns the token for a `0'c` character-code literal.
+    ///
+    /// `c` may be a plain char (`0'a` → 97), the doubled quote `0''` (→ 39), or a `\` escape
+    /// using the same grammar as quoted tokens (`0'\n` → 10).
+    fn lex_char_code(&mut self, line: u32, col: u32, start: usize) -> Option<Spanned<RawToken>> {
+        self.buf.clear();
+        // Account for both the leading `0` and the `'`.
+        self.loc.col += 2;
+        loop {
+            match self.bump() {
+                Some('\\') => {
+                    self.loc.bump_col();
+                    match self.lex_escape() {
+                        Ok(Some(decoded)) => {
+                            let end = self.pos;
+                            return Some(Spanned { value: RawToken::Int(decoded as i64), span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
+                        }
+                        Ok(None) => continue,
+                        Err(kind) => {
+                            let value = RawToken::Err(LexError { loc: Loc { line, col }, kind: kind });
+                            return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (self.pos - start) as u32, offset: start } });
+                        }
+                    }
+                }
+                Some(ch) => {
+                    self.loc.bump_col();
+                    let end = self.pos;
+                    return Some(Spanned { value: RawToken::Int(ch as i64), span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
+                }
+                None => {
+                    let value = RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::UnclosedQuote });
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (self.pos - start) as u32, offset: start } });
                 }
             }
         }
     }
 
     /// Returns the token for a decimal number.
-    fn lex_decimal(&mut self, first: char) -> Option<Token> {
-        let line = self.line;
-        let col = self.col;
+    fn lex_decimal(&mut self, first: char) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
         let mut seen_dot = first == '.';
         let mut seen_e = false;
         self.buf.push(first);
         loop {
-            match self.inner.next() {
+            match self.bump() {
                 Some(ch) if ch.is_digit(10) => self.buf.push(ch),
                 Some('_') => self.buf.push('_'),
                 Some('.') => {
                     if seen_dot {
-                        let tok = match self.buf.parse::<f64>() {
-                            Ok(x) => Token::Float(line, col, x),
-                            Err(_) => Token::Err(line, col, "cannot parse number"),
+                        let value = match self.buf.parse::<f64>() {
+                            Ok(x) => RawToken::Float(x),
+                            Err(_) => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::MalformedFloat }),
                         };
-                        self.col += self.buf.len() as u32;
+                        self.loc.col += self.buf.len() as u32;
+                        let end = self.pos - '.'.len_utf8();
                         self.buf.clear();
                         self.buf.push('.');
-                        return Some(tok);
+                        return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                     }
                     self.buf.push('.');
                     seen_dot = true;
                 }
                 Some('e') => {
                     if seen_e {
-                        let tok = match self.buf.parse::<f64>() {
-                            Ok(x) => Token::Float(line, col, x),
-                            Err(_) => Token::Err(line, col, "cannot parse number"),
+                        let value = match self.buf.parse::<f64>() {
+                            Ok(x) => RawToken::Float(x),
+                            Err(_) => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::MalformedFloat }),
                         };
-                        self.col += self.buf.len() as u32;
+                        self.loc.col += self.buf.len() as u32;
+                        let end = self.pos - 'e'.len_utf8();
                         self.buf.clear();
                         self.buf.push('e');
-                        return Some(tok);
+                        return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                     }
                     self.buf.push('e');
                     seen_dot = true;
                     seen_e = true;
-                    match self.inner.next() {
+                    match self.bump() {
                         Some('-') => self.buf.push('-'),
                         Some(ch) if ch.is_digit(10) => self.buf.push(ch),
                         Some(ch) => {
-                            let tok = match self.buf.parse::<f64>() {
-                                Ok(x) => Token::Float(line, col, x),
-                                Err(_) => Token::Err(line, col, "cannot parse number"),
+                            let value = match self.buf.parse::<f64>() {
+                                Ok(x) => RawToken::Float(x),
+                                Err(_) => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::MalformedFloat }),
                             };
-                            self.col += self.buf.len() as u32;
+                            self.loc.col += self.buf.len() as u32;
+                            let end = self.pos - ch.len_utf8();
                             self.buf.clear();
                             self.buf.push(ch);
-                            return Some(tok);
+                            return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                         }
                         None => {
-                            let tok = match self.buf.parse::<f64>() {
-                                Ok(x) => Token::Float(line, col, x),
-                                Err(_) => Token::Err(line, col, "cannot parse number"),
+                            let value = match self.buf.parse::<f64>() {
+                                Ok(x) => RawToken::Float(x),
+                                Err(_) => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::MalformedFloat }),
                             };
-                            self.col += self.buf.len() as u32;
+                            self.loc.col += self.buf.len() as u32;
+                            let end = self.pos;
                             self.buf.clear();
-                            return Some(tok);
+                            return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                         }
                     }
                 }
                 Some(ch) => {
-                    let tok = if seen_dot {
+                    let value = if seen_dot {
                         match self.buf.parse::<f64>() {
-                            Ok(x) => Token::Float(line, col, x),
-                            Err(_) => Token::Err(line, col, "cannot parse number"),
+                            Ok(x) => RawToken::Float(x),
+                            Err(_) => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::MalformedFloat }),
                         }
                     } else {
                         match self.buf.parse::<i64>() {
-                            Ok(x) => Token::Int(line, col, x),
-                            Err(_) => Token::Err(line, col, "cannot parse number"),
+                            Ok(x) => RawToken::Int(x),
+                            Err(_) => match parse_big_int(self.buf.as_str(), 10) {
+                                Some(n) => RawToken::BigInt(n),
+                                None => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::NumberOutOfRange }),
+                            },
                         }
                     };
-                    self.col += self.buf.len() as u32;
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos - ch.len_utf8();
                     self.buf.clear();
                     self.buf.push(ch);
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
                 None => {
-                    let tok = if seen_dot {
+                    let value = if seen_dot {
                         match self.buf.parse::<f64>() {
-                            Ok(x) => Token::Float(line, col, x),
-                            Err(_) => Token::Err(line, col, "cannot parse number"),
+                            Ok(x) => RawToken::Float(x),
+                            Err(_) => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::MalformedFloat }),
                         }
                     } else {
                         match self.buf.parse::<i64>() {
-                            Ok(x) => Token::Int(line, col, x),
-                            Err(_) => Token::Err(line, col, "cannot parse number"),
+                            Ok(x) => RawToken::Int(x),
+                            Err(_) => match parse_big_int(self.buf.as_str(), 10) {
+                                Some(n) => RawToken::BigInt(n),
+                                None => RawToken::Err(LexError { loc: Loc { line, col }, kind: ErrorKind::NumberOutOfRange }),
+                            },
                         }
                     };
-                    self.col += self.buf.len() as u32;
+                    self.loc.col += self.buf.len() as u32;
+                    let end = self.pos;
                     self.buf.clear();
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
             }
         }
     }
 
-    /// Retuns a token giving the text of a comment.
-    fn lex_comment(&mut self) -> Option<Token> {
-        while let Some(ch) = self.inner.next() {
+    /// Retuns a token giving the text of a `%` line comment.
+    ///
+    /// The comment is discarded and scanning resumes with the next token, unless this lexer was
+    /// constructed with `keep_comments`, in which case a `RawToken::Comment` is returned instead.
+    fn lex_comment(&mut self) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
+        while let Some(ch) = self.bump() {
             if ch == '\n' {
                 break;
             }
+            if self.keep_comments {
+                self.buf.push(ch);
+            }
+        }
+        let end = self.pos;
+        self.loc.bump_line();
+        if self.keep_comments {
+            let text = self.take_text();
+            return Some(Spanned { value: RawToken::Comment(text), span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
         }
-        self.line += 1;
-        self.col = 1;
         self.next()
     }
 
+    /// Returns the token for a symbol starting with `/`, handling the special case of a
+    /// `/* ... */` block comment.
+    ///
+    /// A lone `/` (not followed by `*`) still falls through to [`lex_symbolic`][Self::lex_symbolic]
+    /// so it can combine with neighboring symbolic characters (`/=`, `//`, etc.) as usual.
+    fn lex_slash(&mut self) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
+        self.buf.push('/');
+        match self.bump() {
+            Some('*') => self.lex_block_comment(line, col, start),
+            Some(ch) if is_symbolic(ch) => self.lex_symbolic(ch),
+            Some(ch) => {
+                self.loc.bump_col();
+                let end = self.pos - ch.len_utf8();
+                let value = RawToken::Funct(self.take_text());
+                self.buf.push(ch);
+                Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } })
+            }
+            None => {
+                self.loc.bump_col();
+                let end = self.pos;
+                let value = RawToken::Funct(self.take_text());
+                Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } })
+            }
+        }
+    }
+
+    /// Returns a token giving the text of a `/* ... */` block comment, starting just after the
+    /// opening `/*` has already been consumed.
+    ///
+    /// Nesting is tracked with a depth counter, so `/* a /* b */ c */` closes only at the final
+    /// `*/`. An `UnclosedComment` error is returned if EOF is reached before the comment closes.
+    fn lex_block_comment(&mut self, line: u32, col: u32, start: usize) -> Option<Spanned<RawToken>> {
+        self.buf.clear();
+        self.loc.col += 2;
+        let mut depth: u32 = 1;
+        let mut prev_star = false;
+        let mut prev_slash = false;
+        loop {
+            let ch = match self.bump() {
+                Some(ch) => ch,
+                None => {
+                    self.buf.clear();
+                    let value = RawToken::Err(LexError { loc: Loc { line: self.loc.line, col: self.loc.col }, kind: ErrorKind::UnclosedComment });
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (self.pos - start) as u32, offset: start } });
+                }
+            };
+
+            if ch == '\n' {
+                if self.keep_comments {
+                    if prev_star { self.buf.push('*'); }
+                    if prev_slash { self.buf.push('/'); }
+                    self.buf.push('\n');
+                }
+                prev_star = false;
+                prev_slash = false;
+                self.loc.bump_line();
+                continue;
+            }
+
+            if prev_star && ch == '/' {
+                depth -= 1;
+                self.loc.bump_col();
+                prev_star = false;
+                if depth == 0 {
+                    let end = self.pos;
+                    if self.keep_comments {
+                        let text = self.take_text();
+                        return Some(Spanned { value: RawToken::Comment(text), span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
+                    }
+                    self.buf.clear();
+                    return self.next();
+                }
+                if self.keep_comments { self.buf.push('*'); self.buf.push('/'); }
+                continue;
+            }
+
+            if prev_slash && ch == '*' {
+                depth += 1;
+                self.loc.bump_col();
+                prev_slash = false;
+                if self.keep_comments { self.buf.push('/'); self.buf.push('*'); }
+                continue;
+            }
+
+            if self.keep_comments {
+                if prev_star { self.buf.push('*'); }
+                if prev_slash { self.buf.push('/'); }
+            }
+            prev_star = false;
+            prev_slash = false;
+
+            if ch == '*' {
+                prev_star = true;
+                self.loc.bump_col();
+                continue;
+            }
+            if ch == '/' {
+                prev_slash = true;
+                self.loc.bump_col();
+                continue;
+            }
+
+            self.loc.bump_col();
+            if self.keep_comments { self.buf.push(ch); }
+        }
+    }
+
     /// Returns a Functor or String for a token enclosed in quotes.
     ///
     /// Escape sequences are replaced and the token will not include the surrounding quotes.
     /// An Err token is returned if the quote is unclosed.
-    fn lex_quote(&mut self, quote: char) -> Option<Token> {
-        let line = self.line;
-        let col = self.col;
-        self.col += 1;
+    fn lex_quote(&mut self, quote: char) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
+        self.loc.bump_col();
         loop {
-            match self.inner.next() {
+            match self.bump() {
                 Some('\\') => {
-                    self.col += 2;
-                    match self.inner.next() {
-                        Some('n') => self.buf.push('\n'),
-                        Some('r') => self.buf.push('\r'),
-                        Some('t') => self.buf.push('\t'),
-                        Some('\\') => self.buf.push('\\'),
-                        Some(ch) => self.buf.push(ch),
-                        None => {
+                    self.loc.bump_col();
+                    match self.lex_escape() {
+                        Ok(Some(decoded)) => self.buf.push(decoded),
+                        Ok(None) => {}
+                        Err(kind) => {
                             self.buf.clear();
-                            return Some(Token::Err(line, col, "unclosed quote"));
+                            let value = RawToken::Err(LexError { loc: Loc { line, col }, kind: kind });
+                            return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (self.pos - start) as u32, offset: start } });
                         }
-                    };
+                    }
                 }
                 Some('\n') => {
-                    self.col = 1;
-                    self.line += 1;
+                    self.loc.bump_line();
                     self.buf.push('\n');
                 }
                 Some(ch) if ch == quote => {
-                    self.col += 1;
-                    let tok = match quote {
-                        '\"' => Token::Str(line, col, self.get_symbol()),
-                        '\'' => Token::Funct(line, col, self.get_symbol()),
+                    self.loc.bump_col();
+                    let end = self.pos;
+                    let value = match quote {
+                        '\"' => RawToken::Str(self.take_text()),
+                        '\'' => RawToken::Funct(self.take_text()),
                         _ => panic!("unsupported quote"),
                     };
-                    self.buf.clear();
-                    return Some(tok);
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } });
                 }
                 Some(ch) => {
-                    self.col += 1;
+                    self.loc.bump_col();
                     self.buf.push(ch);
                 }
                 None => {
                     self.buf.clear();
-                    return Some(Token::Err(self.line, self.col, "unclosed quote"));
+                    let value = RawToken::Err(LexError { loc: Loc { line: self.loc.line, col: self.loc.col }, kind: ErrorKind::UnclosedQuote });
+                    return Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (self.pos - start) as u32, offset: start } });
+                }
+            }
+        }
+    }
+
+    /// Decodes a `\` escape immediately following an already-consumed backslash, advancing
+    /// `self.loc.col` for every character consumed.
+    ///
+    /// Returns the decoded character, or `None` for a line continuation (a backslash-newline,
+    /// which contributes no character to the token). Shared by [`lex_quote`][Self::lex_quote]
+    /// and [`lex_char_code`][Self::lex_char_code], which both scan the same escape grammar.
+    fn lex_escape(&mut self) -> Result<Option<char>, ErrorKind> {
+        match self.bump() {
+            Some('a') => { self.loc.bump_col(); Ok(Some('\u{7}')) }
+            Some('b') => { self.loc.bump_col(); Ok(Some('\u{8}')) }
+            Some('f') => { self.loc.bump_col(); Ok(Some('\u{c}')) }
+            Some('n') => { self.loc.bump_col(); Ok(Some('\n')) }
+            Some('r') => { self.loc.bump_col(); Ok(Some('\r')) }
+            Some('t') => { self.loc.bump_col(); Ok(Some('\t')) }
+            Some('v') => { self.loc.bump_col(); Ok(Some('\u{b}')) }
+            Some('s') => { self.loc.bump_col(); Ok(Some(' ')) }
+            Some('0') => { self.loc.bump_col(); Ok(Some('\u{0}')) }
+            Some('\\') => { self.loc.bump_col(); Ok(Some('\\')) }
+            Some('\'') => { self.loc.bump_col(); Ok(Some('\'')) }
+            Some('\"') => { self.loc.bump_col(); Ok(Some('\"')) }
+            Some('`') => { self.loc.bump_col(); Ok(Some('`')) }
+            Some('\n') => {
+                // A backslash-newline is a line continuation: it is removed entirely and
+                // contributes nothing to the token's text.
+                self.loc.bump_line();
+                Ok(None)
+            }
+            Some(ch) if ch.is_digit(8) => {
+                self.loc.bump_col();
+                self.lex_octal_escape(ch).map(Some).ok_or(ErrorKind::InvalidEscape)
+            }
+            Some('x') => {
+                self.loc.bump_col();
+                self.lex_hex_escape().map(Some).ok_or(ErrorKind::InvalidEscape)
+            }
+            Some(_) => Err(ErrorKind::InvalidEscape),
+            None => Err(ErrorKind::UnclosedQuote),
+        }
+    }
+
+    /// Decodes an octal escape sequence of the form `\NNN\` into its code point.
+    ///
+    /// `first` is the leading octal digit, already consumed by the caller. Returns `None`
+    /// if the sequence is not terminated by a backslash or decodes to an invalid code point.
+    fn lex_octal_escape(&mut self, first: char) -> Option<char> {
+        let mut digits = String::new();
+        digits.push(first);
+        loop {
+            match self.bump() {
+                Some('\\') => {
+                    self.loc.bump_col();
+                    break;
+                }
+                Some(ch) if ch.is_digit(8) => {
+                    self.loc.bump_col();
+                    digits.push(ch);
+                }
+                _ => return None,
+            }
+        }
+        u32::from_str_radix(&digits, 8).ok().and_then(char::from_u32)
+    }
+
+    /// Decodes a hex escape sequence of the form `\xNN\` into its code point.
+    ///
+    /// Returns `None` if no digits are present, the sequence is not terminated by a
+    /// backslash, or it decodes to an invalid code point.
+    fn lex_hex_escape(&mut self) -> Option<char> {
+        let mut digits = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') if !digits.is_empty() => {
+                    self.loc.bump_col();
+                    break;
                 }
+                Some(ch) if ch.is_digit(16) => {
+                    self.loc.bump_col();
+                    digits.push(ch);
+                }
+                _ => return None,
             }
         }
+        u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
     }
 
     /// Returns the token for a single char symbol.
-    fn lex_simple(&mut self, ch: char) -> Option<Token> {
-        let line = self.line;
-        let col = self.col;
-        self.col += 1;
-        match ch {
-            '(' => Some(Token::ParenOpen(line, col)),
-            ')' => Some(Token::ParenClose(line, col)),
-            '[' => Some(Token::BracketOpen(line, col)),
-            ']' => Some(Token::BracketClose(line, col)),
-            '{' => Some(Token::BraceOpen(line, col)),
-            '}' => Some(Token::BraceClose(line, col)),
-            ',' => Some(Token::Comma(line, col)),
-            '|' => Some(Token::Bar(line, col)),
-            '.' => Some(Token::Dot(line, col)),
+    fn lex_simple(&mut self, ch: char) -> Option<Spanned<RawToken>> {
+        let line = self.loc.line;
+        let col = self.loc.col;
+        let start = self.start;
+        let end = self.pos;
+        self.loc.bump_col();
+        let value = match ch {
+            '(' => RawToken::ParenOpen,
+            ')' => RawToken::ParenClose,
+            '[' => RawToken::BracketOpen,
+            ']' => RawToken::BracketClose,
+            '{' => RawToken::BraceOpen,
+            '}' => RawToken::BraceClose,
+            ',' => RawToken::Comma,
+            '|' => RawToken::Bar,
+            '.' => RawToken::Dot,
             _ => panic!("lex_simple called without a grouping symbol"),
-        }
+        };
+        Some(Spanned { value: value, span: Span { start: Loc { line, col }, len: (end - start) as u32, offset: start } })
     }
 
     /// Returns the token following the current span of whitespace/control characters.
-    fn lex_space(&mut self, first: char) -> Option<Token> {
+    fn lex_space(&mut self, first: char) -> Option<Spanned<RawToken>> {
         let mut ch = Some(first);
         loop {
             match ch {
                 Some('\n') => {
-                    self.line += 1;
-                    self.col = 1;
+                    self.loc.bump_line();
                 }
                 Some(ch) if ch.is_whitespace() || ch.is_control() => {
-                    self.col += 1;
+                    self.loc.bump_col();
                 }
                 Some(ch) => {
                     self.buf.push(ch);
@@ -481,15 +933,79 @@ impl<'ns, I> Lexer<'ns, I>
                 }
                 None => return None,
             };
-            ch = self.inner.next();
+            ch = self.bump();
+        }
+    }
+}
+
+/// An iterator over spanned, interned `Token`s.
+///
+/// `Lexer` is a thin adapter over a [`RawLexer`]: it does no scanning of its own, it just interns
+/// the text carried by each `RawToken` into a `NameSpace`.
+pub struct Lexer<'ns, I> {
+    inner: RawLexer<I>,
+    ns: &'ns NameSpace,
+}
+
+impl<'ns, I> Lexer<'ns, I>
+    where I: Iterator<Item = char>
+{
+    pub fn new(chars: I, ns: &'ns NameSpace) -> Lexer<'ns, I> {
+        Lexer {
+            inner: RawLexer::new(chars),
+            ns: ns,
+        }
+    }
+
+    /// Like [`Lexer::new`], but comments are yielded as `Token::Comment` tokens instead of being
+    /// discarded.
+    pub fn new_keep_comments(chars: I, ns: &'ns NameSpace) -> Lexer<'ns, I> {
+        Lexer {
+            inner: RawLexer::new_keep_comments(chars),
+            ns: ns,
         }
     }
 }
 
+impl<'ns, I> Iterator for Lexer<'ns, I>
+    where I: Iterator<Item = char>
+{
+    type Item = Spanned<Token>;
+    fn next(&mut self) -> Option<Spanned<Token>> {
+        self.inner.next().map(|Spanned { value, span }| {
+            let value = match value {
+                RawToken::Err(e) => Token::Err(e),
+                RawToken::Funct(text) => Token::Funct(self.ns.intern(&text)),
+                RawToken::Str(text) => Token::Str(self.ns.intern(&text)),
+                RawToken::Var(text) => Token::Var(self.ns.intern(&text)),
+                RawToken::Int(x) => Token::Int(x),
+                RawToken::BigInt(x) => Token::BigInt(x),
+                RawToken::Float(x) => Token::Float(x),
+                RawToken::ParenOpen => Token::ParenOpen,
+                RawToken::ParenClose => Token::ParenClose,
+                RawToken::BracketOpen => Token::BracketOpen,
+                RawToken::BracketClose => Token::BracketClose,
+                RawToken::BraceOpen => Token::BraceOpen,
+                RawToken::BraceClose => Token::BraceClose,
+                RawToken::Bar => Token::Bar,
+                RawToken::Comma => Token::Comma,
+                RawToken::Dot => Token::Dot,
+                RawToken::Comment(text) => Token::Comment(self.ns.intern(&text)),
+            };
+            Spanned { value: value, span: span }
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn assert_tok<T: PartialEq + ::std::fmt::Debug>(got: Spanned<T>, value: T, line: u32, col: u32) {
+        assert_eq!(got.value, value);
+        assert_eq!(got.span.start, Loc { line: line, col: col });
+    }
+
     #[test]
     #[cfg_attr(rustfmt, rustfmt_skip)] // TODO: #[rustfmt_skip] once custom attributes stabilize
     fn basic() {
@@ -501,26 +1017,26 @@ mod test {
                   \t\t   \t\n";
         let ns = NameSpace::new();
         let mut toks = Lexer::new(pl.chars(), &ns);
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 1, ns.intern("_abcd")));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 7, ns.intern("ABCD")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 12, ns.intern("foobar")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 19, ns.intern("hello world")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 33, ns.intern("+++")));
-        assert_eq!(toks.next().unwrap(), Token::Int(3, 1, 123));
-        assert_eq!(toks.next().unwrap(), Token::Float(3, 5, 456.789));
-        assert_eq!(toks.next().unwrap(), Token::Float(3, 13, 8.765e43));
-        assert_eq!(toks.next().unwrap(), Token::Float(3, 22, 1e-1));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 1, 0xDEADBEEF));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 12, 0o644));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 18, 0b11001100));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 29, 0987654321));
-        assert_eq!(toks.next().unwrap(), Token::Float(4, 40, 0.123));
-        assert_eq!(toks.next().unwrap(), Token::Funct(5, 1, ns.intern("->")));
-        assert_eq!(toks.next().unwrap(), Token::Int(5, 4, -0xff));
-        assert_eq!(toks.next().unwrap(), Token::Float(5, 10, -1.23));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(5, 16));
-        assert_eq!(toks.next().unwrap(), Token::Funct(5, 17, ns.intern("-")));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(5, 18));
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("_abcd")), 1, 1);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("ABCD")), 1, 7);
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("foobar")), 1, 12);
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("hello world")), 1, 19);
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("+++")), 1, 33);
+        assert_tok(toks.next().unwrap(), Token::Int(123), 3, 1);
+        assert_tok(toks.next().unwrap(), Token::Float(456.789), 3, 5);
+        assert_tok(toks.next().unwrap(), Token::Float(8.765e43), 3, 13);
+        assert_tok(toks.next().unwrap(), Token::Float(1e-1), 3, 22);
+        assert_tok(toks.next().unwrap(), Token::Int(0xDEADBEEF), 4, 1);
+        assert_tok(toks.next().unwrap(), Token::Int(0o644), 4, 12);
+        assert_tok(toks.next().unwrap(), Token::Int(0b11001100), 4, 18);
+        assert_tok(toks.next().unwrap(), Token::Int(0987654321), 4, 29);
+        assert_tok(toks.next().unwrap(), Token::Float(0.123), 4, 40);
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("->")), 5, 1);
+        assert_tok(toks.next().unwrap(), Token::Int(-0xff), 5, 4);
+        assert_tok(toks.next().unwrap(), Token::Float(-1.23), 5, 10);
+        assert_tok(toks.next().unwrap(), Token::ParenOpen, 5, 16);
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("-")), 5, 17);
+        assert_tok(toks.next().unwrap(), Token::ParenClose, 5, 18);
         assert_eq!(toks.next(), None);
     }
 
@@ -533,38 +1049,195 @@ mod test {
         let mut toks = Lexer::new(pl.chars(), &ns);
 
         // member(H, [H|T]).
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, ns.intern("member")));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(1, 7));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 8, ns.intern("H")));
-        assert_eq!(toks.next().unwrap(), Token::Comma(1, 9));
-        assert_eq!(toks.next().unwrap(), Token::BracketOpen(1, 11));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 12, ns.intern("H")));
-        assert_eq!(toks.next().unwrap(), Token::Bar(1, 13));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 14, ns.intern("T")));
-        assert_eq!(toks.next().unwrap(), Token::BracketClose(1, 15));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(1, 16));
-        assert_eq!(toks.next().unwrap(), Token::Dot(1, 17));
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("member")), 1, 1);
+        assert_tok(toks.next().unwrap(), Token::ParenOpen, 1, 7);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("H")), 1, 8);
+        assert_tok(toks.next().unwrap(), Token::Comma, 1, 9);
+        assert_tok(toks.next().unwrap(), Token::BracketOpen, 1, 11);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("H")), 1, 12);
+        assert_tok(toks.next().unwrap(), Token::Bar, 1, 13);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("T")), 1, 14);
+        assert_tok(toks.next().unwrap(), Token::BracketClose, 1, 15);
+        assert_tok(toks.next().unwrap(), Token::ParenClose, 1, 16);
+        assert_tok(toks.next().unwrap(), Token::Dot, 1, 17);
 
         // member(X, [_|T]) :- member(X, T).
-        assert_eq!(toks.next().unwrap(), Token::Funct(2, 1, ns.intern("member")));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(2, 7));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 8, ns.intern("X")));
-        assert_eq!(toks.next().unwrap(), Token::Comma(2, 9));
-        assert_eq!(toks.next().unwrap(), Token::BracketOpen(2, 11));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 12, ns.intern("_")));
-        assert_eq!(toks.next().unwrap(), Token::Bar(2, 13));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 14, ns.intern("T")));
-        assert_eq!(toks.next().unwrap(), Token::BracketClose(2, 15));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(2, 16));
-        assert_eq!(toks.next().unwrap(), Token::Funct(2, 18, ns.intern(":-")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(2, 21, ns.intern("member")));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(2, 27));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 28, ns.intern("X")));
-        assert_eq!(toks.next().unwrap(), Token::Comma(2, 29));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 31, ns.intern("T")));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(2, 32));
-        assert_eq!(toks.next().unwrap(), Token::Dot(2, 33));
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("member")), 2, 1);
+        assert_tok(toks.next().unwrap(), Token::ParenOpen, 2, 7);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("X")), 2, 8);
+        assert_tok(toks.next().unwrap(), Token::Comma, 2, 9);
+        assert_tok(toks.next().unwrap(), Token::BracketOpen, 2, 11);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("_")), 2, 12);
+        assert_tok(toks.next().unwrap(), Token::Bar, 2, 13);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("T")), 2, 14);
+        assert_tok(toks.next().unwrap(), Token::BracketClose, 2, 15);
+        assert_tok(toks.next().unwrap(), Token::ParenClose, 2, 16);
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern(":-")), 2, 18);
+        assert_tok(toks.next().unwrap(), Token::Funct(ns.intern("member")), 2, 21);
+        assert_tok(toks.next().unwrap(), Token::ParenOpen, 2, 27);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("X")), 2, 28);
+        assert_tok(toks.next().unwrap(), Token::Comma, 2, 29);
+        assert_tok(toks.next().unwrap(), Token::Var(ns.intern("T")), 2, 31);
+        assert_tok(toks.next().unwrap(), Token::ParenClose, 2, 32);
+        assert_tok(toks.next().unwrap(), Token::Dot, 2, 33);
+
+        assert_eq!(toks.next(), None);
+    }
+
+    #[test]
+    fn spans_are_byte_offsets_into_the_source() {
+        let pl = "foo(X, 'a b').";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.chars(), &ns);
+
+        fn text<'a>(pl: &'a str, span: Span) -> &'a str {
+            &pl[span.offset..span.offset + span.len as usize]
+        }
+
+        let funct = toks.next().unwrap();
+        assert_eq!(funct.value, Token::Funct(ns.intern("foo")));
+        assert_eq!(funct.span, Span { start: Loc { line: 1, col: 1 }, offset: 0, len: 3 });
+        assert_eq!(text(pl, funct.span), "foo");
+
+        let paren_open = toks.next().unwrap();
+        assert_eq!(paren_open.span, Span { start: Loc { line: 1, col: 4 }, offset: 3, len: 1 });
+
+        let var = toks.next().unwrap();
+        assert_eq!(var.value, Token::Var(ns.intern("X")));
+        assert_eq!(var.span, Span { start: Loc { line: 1, col: 5 }, offset: 4, len: 1 });
+        assert_eq!(text(pl, var.span), "X");
+
+        let comma = toks.next().unwrap();
+        assert_eq!(comma.span, Span { start: Loc { line: 1, col: 6 }, offset: 5, len: 1 });
+
+        let quoted = toks.next().unwrap();
+        assert_eq!(quoted.value, Token::Funct(ns.intern("a b")));
+        assert_eq!(quoted.span, Span { start: Loc { line: 1, col: 8 }, offset: 7, len: 5 });
+        assert_eq!(text(pl, quoted.span), "'a b'");
+
+        let paren_close = toks.next().unwrap();
+        assert_eq!(paren_close.span, Span { start: Loc { line: 1, col: 13 }, offset: 12, len: 1 });
+
+        let dot = toks.next().unwrap();
+        assert_eq!(dot.span, Span { start: Loc { line: 1, col: 14 }, offset: 13, len: 1 });
+
+        assert_eq!(toks.next(), None);
+    }
+
+    #[test]
+    fn escape_sequences_in_quoted_tokens() {
+        let pl = "\"\\a\\b\\f\\n\\r\\t\\v\\s\\0\\\\\\'\\\"\\`\" '\\101\\' '\\x2a\\' 'foo\\\nbar' 'bad\\q";
+        let mut toks = RawLexer::new(pl.chars());
+
+        match toks.next().unwrap().value {
+            RawToken::Str(text) => {
+                assert_eq!(text, "\u{7}\u{8}\u{c}\n\r\t\u{b} \u{0}\\\'\"`");
+            }
+            other => panic!("expected Str, got {:?}", other),
+        }
+
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "A"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
+
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "*"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
+
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "foobar"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
+
+        match toks.next().unwrap().value {
+            RawToken::Err(err) => assert_eq!(err.kind, ErrorKind::InvalidEscape),
+            other => panic!("expected Err, got {:?}", other),
+        }
+
+        assert_eq!(toks.next(), None);
+    }
+
+    #[test]
+    fn char_code_literals() {
+        let pl = "0'a 0'' 0'\\n 0'\\101\\";
+        let mut toks = RawLexer::new(pl.chars());
+
+        assert_tok(toks.next().unwrap(), RawToken::Int(97), 1, 1);
+        assert_tok(toks.next().unwrap(), RawToken::Int(39), 1, 5);
+        assert_tok(toks.next().unwrap(), RawToken::Int(10), 1, 9);
+        assert_tok(toks.next().unwrap(), RawToken::Int(65), 1, 14);
+        assert_eq!(toks.next(), None);
+    }
+
+    #[test]
+    fn integers_too_big_for_i64_fall_back_to_big_int() {
+        let pl = "99999999999999999999999999 0xffffffffffffffffff";
+        let mut toks = RawLexer::new(pl.chars());
+
+        let expected_dec = "99999999999999999999999999".parse::<BigInt>().unwrap();
+        assert_tok(toks.next().unwrap(), RawToken::BigInt(expected_dec), 1, 1);
+
+        let expected_hex = parse_big_int("ffffffffffffffffff", 16).unwrap();
+        assert_tok(toks.next().unwrap(), RawToken::BigInt(expected_hex), 1, 28);
+
+        assert_eq!(toks.next(), None);
+    }
+
+    #[test]
+    fn nested_block_comments_are_discarded_by_default() {
+        let pl = "foo /* a /* b */ c */ bar";
+        let mut toks = RawLexer::new(pl.chars());
+
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "foo"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "bar"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
+        assert_eq!(toks.next(), None);
+    }
+
+    #[test]
+    fn unclosed_block_comment_is_an_error() {
+        let pl = "foo /* a /* b */ c";
+        let mut toks = RawLexer::new(pl.chars());
+
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "foo"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
+        match toks.next().unwrap().value {
+            RawToken::Err(err) => assert_eq!(err.kind, ErrorKind::UnclosedComment),
+            other => panic!("expected Err, got {:?}", other),
+        }
+        assert_eq!(toks.next(), None);
+    }
+
+    #[test]
+    fn block_comments_are_kept_as_tokens_when_requested() {
+        let pl = "foo /* a /* b */ c */ bar";
+        let mut toks = RawLexer::new_keep_comments(pl.chars());
 
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "foo"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
+        let comment = toks.next().unwrap();
+        match comment.value {
+            RawToken::Comment(text) => {
+                assert_eq!(comment.span.start, Loc { line: 1, col: 5 });
+                assert_eq!(text, " a /* b */ c ");
+            }
+            other => panic!("expected Comment, got {:?}", other),
+        }
+        match toks.next().unwrap().value {
+            RawToken::Funct(text) => assert_eq!(text, "bar"),
+            other => panic!("expected Funct, got {:?}", other),
+        }
         assert_eq!(toks.next(), None);
     }
-}
\ No newline at end of file
+}
@@ -1,14 +1,14 @@
 #![feature(custom_attribute)]
 #![feature(box_syntax, box_patterns)]
+#![feature(try_reserve)]
 
-#[macro_use]
-extern crate lazy_static;
-
+extern crate num_bigint;
 extern crate ordered_float;
 extern crate rand;
-extern crate regex;
 extern crate unicode_normalization;
 
 pub mod collections;
 pub mod db;
+pub mod lexer;
+pub mod namespace;
 pub mod syntax;
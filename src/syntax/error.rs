@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt;
+use std::ops::Range;
 
 /// A type alias for results with possible `SyntaxError`s.
 pub type Result<T> = ::std::result::Result<T, SyntaxError>;
@@ -9,6 +10,7 @@ pub type Result<T> = ::std::result::Result<T, SyntaxError>;
 pub struct SyntaxError {
     line: usize,
     col: usize,
+    span: Range<usize>,
     kind: Kind,
 }
 
@@ -17,6 +19,8 @@ enum Kind {
     PrioirtyClash,
     Unbalanced(char),
     Unexpected(&'static str),
+    UnterminatedComment,
+    ConfusingUnicode(char),
     Wrapper(Box<Error + Send + Sync>),
 
     // Emitted when using an incomplete feature.
@@ -24,35 +28,50 @@ enum Kind {
 }
 
 impl SyntaxError {
-    fn new(line: usize, col: usize, kind: Kind) -> SyntaxError {
+    fn new(line: usize, col: usize, offset: usize, kind: Kind) -> SyntaxError {
         SyntaxError {
             line: line,
             col: col,
+            span: offset..offset + 1,
             kind: kind,
         }
     }
 
-    pub fn wrap<E>(line: usize, col: usize, err: E) -> SyntaxError
+    pub fn wrap<E>(line: usize, col: usize, offset: usize, err: E) -> SyntaxError
     where
         E: Into<Box<Error + Send + Sync>>,
     {
-        SyntaxError::new(line, col, Kind::Wrapper(err.into()))
+        SyntaxError::new(line, col, offset, Kind::Wrapper(err.into()))
     }
 
-    pub fn priority_clash(line: usize, col: usize) -> SyntaxError {
-        SyntaxError::new(line, col, Kind::PrioirtyClash)
+    pub fn priority_clash(line: usize, col: usize, offset: usize) -> SyntaxError {
+        SyntaxError::new(line, col, offset, Kind::PrioirtyClash)
     }
 
-    pub fn unbalanced(line: usize, col: usize, ch: char) -> SyntaxError {
-        SyntaxError::new(line, col, Kind::Unbalanced(ch))
+    pub fn unbalanced(line: usize, col: usize, offset: usize, ch: char) -> SyntaxError {
+        SyntaxError::new(line, col, offset, Kind::Unbalanced(ch))
     }
 
-    pub fn unexpected(line: usize, col: usize, s: &'static str) -> SyntaxError {
-        SyntaxError::new(line, col, Kind::Unexpected(s))
+    pub fn unexpected(line: usize, col: usize, offset: usize, s: &'static str) -> SyntaxError {
+        SyntaxError::new(line, col, offset, Kind::Unexpected(s))
     }
 
-    pub fn todo(line: usize, col: usize) -> SyntaxError {
-        SyntaxError::new(line, col, Kind::TODO)
+    /// Constructs an error for a `/* ... */` block comment that reaches
+    /// end-of-input before it is closed. `line` and `col` should point at the
+    /// opening `/*`, not the point of failure.
+    pub fn unterminated_comment(line: usize, col: usize, offset: usize) -> SyntaxError {
+        SyntaxError::new(line, col, offset, Kind::UnterminatedComment)
+    }
+
+    /// Constructs an error for a bidirectional-control codepoint (part of
+    /// the "Trojan Source" family of attacks) found while
+    /// `allow_confusing_unicode` is disabled.
+    pub fn confusing_unicode(line: usize, col: usize, offset: usize, ch: char) -> SyntaxError {
+        SyntaxError::new(line, col, offset, Kind::ConfusingUnicode(ch))
+    }
+
+    pub fn todo(line: usize, col: usize, offset: usize) -> SyntaxError {
+        SyntaxError::new(line, col, offset, Kind::TODO)
     }
 
     /// Returns the line at which the error occurs.
@@ -64,6 +83,34 @@ impl SyntaxError {
     pub fn col(&self) -> usize {
         self.col
     }
+
+    /// Returns the byte-offset span of the offending text into the source
+    /// that produced this error.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Renders a caret diagnostic: the terse one-line message from `Display`,
+    /// followed by the offending line sliced out of `source` and a run of
+    /// `^` carets underneath pointing at `self.span()`.
+    ///
+    /// `source` must be the same text the error was produced from; passing
+    /// any other string won't panic, but the carets will point at whatever
+    /// happens to occupy that byte range in it.
+    pub fn render(&self, source: &str) -> String {
+        let start = self.span.start.min(source.len());
+        let end = self.span.end.min(source.len()).max(start);
+
+        let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = source[start..].find('\n').map(|i| i + start).unwrap_or(source.len());
+        let line_text = &source[line_start..line_end];
+
+        let caret_col = start - line_start;
+        let caret_len = (end - start).max(1);
+        let underline = format!("{}{}", " ".repeat(caret_col), "^".repeat(caret_len));
+
+        format!("{}\n{}\n{}", self, line_text, underline)
+    }
 }
 
 impl Error for SyntaxError {
@@ -72,6 +119,8 @@ impl Error for SyntaxError {
             &Kind::PrioirtyClash => "operator priority clash",
             &Kind::Unbalanced(_) => "unbalanced quote or paren",
             &Kind::Unexpected(_) => "unexpected token",
+            &Kind::UnterminatedComment => "unterminated block comment",
+            &Kind::ConfusingUnicode(_) => "disallowed bidirectional-control codepoint",
             &Kind::TODO => "not yet implemented",
             &Kind::Wrapper(ref e) => e.description(),
         }
@@ -93,6 +142,10 @@ impl<'ctx> fmt::Display for SyntaxError {
             &Kind::PrioirtyClash => write!(f, "operator priority clash"),
             &Kind::Unbalanced(ch) => write!(f, "unbalanced grouping character: '{}'", ch),
             &Kind::Unexpected(tok) => write!(f, "unexpected token: {}", tok),
+            &Kind::UnterminatedComment => write!(f, "unterminated block comment"),
+            &Kind::ConfusingUnicode(ch) => {
+                write!(f, "disallowed bidirectional-control codepoint: U+{:04X}", ch as u32)
+            }
             &Kind::TODO => write!(f, "not yet implemented"),
             &Kind::Wrapper(ref e) => write!(f, "{}", e),
         }
@@ -100,6 +153,11 @@ impl<'ctx> fmt::Display for SyntaxError {
 }
 
 impl PartialEq for SyntaxError {
+    /// Compares only `line` and `col`, like the pre-existing comparison that
+    /// already ignored `kind`: two errors at the same position are "the same
+    /// error" for the purposes of tests and deduplication, even if their
+    /// spans or messages differ in extent. `span` is diagnostic detail, not
+    /// identity.
     fn eq(&self, other: &SyntaxError) -> bool {
         self.line == other.line && self.col == other.col
     }
@@ -12,11 +12,10 @@
 use std::fmt;
 use std::io::BufRead;
 
-use regex::Regex;
 use unicode_normalization::UnicodeNormalization;
 
 use syntax::namespace::{NameSpace, Name};
-use syntax::error::SyntaxError;
+use syntax::error::{SyntaxError, Result};
 
 /// A lexer for logic programs.
 ///
@@ -29,6 +28,12 @@ pub struct Lexer<'ns, B: BufRead> {
     line: usize,
     col: usize,
     skip_space: bool,
+    allow_confusing_unicode: bool,
+    eof_emitted: bool,
+
+    /// The byte offset into the whole source text of the start of the
+    /// currently buffered line, i.e. `offset() == line_offset + col - 1`.
+    line_offset: usize,
 
     // Two buffers: The first holds each line.
     // The second holds the normalized form of the line.
@@ -38,30 +43,37 @@ pub struct Lexer<'ns, B: BufRead> {
 
 /// A lexical item of a logic program.
 ///
-/// Every `Token` includes its line and column as the first two members. When
-/// relevant, the third member gives an interpreted value of the token.
+/// Every `Token` includes its line, column, and byte offset as the first
+/// three members. When relevant, the fourth member gives an interpreted
+/// value of the token.
 ///
 /// Lexical errors are given as a `Token::Err` whose value is the error message.
+///
+/// `Token::Eof` is emitted exactly once, by the iterator, after the last real
+/// token and before it starts returning `None`. It lets a parser match on a
+/// terminal token instead of special-casing the end of the stream, and gives
+/// a concrete position to attach "unexpected end of input" diagnostics to.
 #[derive(Debug)]
 #[derive(PartialEq)]
 pub enum Token<'ns> {
     Err(SyntaxError),
-    Funct(usize, usize, Name<'ns>),
-    Str(usize, usize, Name<'ns>),
-    Var(usize, usize, Name<'ns>),
-    Int(usize, usize, i64),
-    Float(usize, usize, f64),
-    ParenOpen(usize, usize),
-    ParenClose(usize, usize),
-    BracketOpen(usize, usize),
-    BracketClose(usize, usize),
-    BraceOpen(usize, usize),
-    BraceClose(usize, usize),
-    Bar(usize, usize, Name<'ns>),
-    Comma(usize, usize, Name<'ns>),
-    Dot(usize, usize),
-    Space(usize, usize),
-    Comment(usize, usize),
+    Eof(usize, usize, usize),
+    Funct(usize, usize, usize, Name<'ns>),
+    Str(usize, usize, usize, Name<'ns>),
+    Var(usize, usize, usize, Name<'ns>),
+    Int(usize, usize, usize, i64),
+    Float(usize, usize, usize, f64),
+    ParenOpen(usize, usize, usize),
+    ParenClose(usize, usize, usize),
+    BracketOpen(usize, usize, usize),
+    BracketClose(usize, usize, usize),
+    BraceOpen(usize, usize, usize),
+    BraceClose(usize, usize, usize),
+    Bar(usize, usize, usize, Name<'ns>),
+    Comma(usize, usize, usize, Name<'ns>),
+    Dot(usize, usize, usize),
+    Space(usize, usize, usize, Name<'ns>),
+    Comment(usize, usize, usize, Name<'ns>),
 }
 
 // Public API
@@ -78,6 +90,9 @@ impl<'ns, B: BufRead> Lexer<'ns, B> {
             line: 0, // incremented on first line
             col: 1,
             skip_space: true,
+            allow_confusing_unicode: true,
+            eof_emitted: false,
+            line_offset: 0,
             buf_line: String::with_capacity(128),
             buf_norm: String::with_capacity(128),
         }
@@ -85,7 +100,20 @@ impl<'ns, B: BufRead> Lexer<'ns, B> {
 
     /// Toggles whether space and comment tokens are reported.
     pub fn report_space(mut self, yes: bool) -> Self {
-        self.skip_space = yes;
+        self.skip_space = !yes;
+        self
+    }
+
+    /// Toggles whether bidirectional-control codepoints are permitted in
+    /// source text.
+    ///
+    /// These codepoints (U+202A-U+202E, U+2066-U+2069) can reorder how a
+    /// line is *displayed* without changing how it lexes, the "Trojan
+    /// Source" class of attack. When disabled, every freshly read line is
+    /// scanned for them before any token from it is produced, and a
+    /// `Token::Err` is emitted for the first one found.
+    pub fn allow_confusing_unicode(mut self, yes: bool) -> Self {
+        self.allow_confusing_unicode = yes;
         self
     }
 
@@ -98,6 +126,175 @@ impl<'ns, B: BufRead> Lexer<'ns, B> {
     pub fn col(&self) -> usize {
         self.col
     }
+
+    /// Returns the byte offset into the whole source text of the next token
+    /// to be emitted by the lexer.
+    pub fn offset(&self) -> usize {
+        self.line_offset + self.col - 1
+    }
+
+    /// Returns the `NameSpace` used to intern this lexer's tokens.
+    pub fn ns(&self) -> &'ns NameSpace {
+        self.ns
+    }
+
+    /// Ensures that `self.buf_norm[self.col - 1..]` is non-empty, pulling a
+    /// further line from `self.reader` if the current position has run past
+    /// the end of the buffered line.
+    ///
+    /// Advances `self.line` and resets `self.col` when a refill happens.
+    /// Returns `Ok(true)` if more input is available (whether or not a
+    /// refill was needed), `Ok(false)` at end-of-input, and `Err` if the
+    /// underlying reader fails.
+    ///
+    /// Used directly by `Iterator::next`, and by the quote and block-comment
+    /// scanners, which are the only tokens that legitimately cross line
+    /// boundaries.
+    fn ensure_input(&mut self) -> Result<bool> {
+        if self.buf_norm.len() >= self.col {
+            return Ok(true);
+        }
+
+        self.line_offset += self.buf_line.len();
+        self.line += 1;
+        self.col = 1;
+        self.buf_line.clear();
+        match self.reader.read_line(&mut self.buf_line) {
+            Ok(0) => {
+                // No more input. Clear the normalized buffer too, or the
+                // `buf_norm.len() >= self.col` guard above would spuriously
+                // think there's still unconsumed input on the next call.
+                self.buf_norm.clear();
+                return Ok(false);
+            }
+            Ok(_) => (),
+            Err(e) => return Err(SyntaxError::wrap(self.line, self.col, self.offset(), e)),
+        }
+
+        // Perform Unicode normalization.
+        // This has security, usability, and performance implications.
+        self.buf_norm.clear();
+        self.buf_norm.extend(self.buf_line.nfkc());
+
+        if !self.allow_confusing_unicode {
+            if let Some((col, ch)) = find_bidi_control(&self.buf_norm) {
+                let offset = self.line_offset + col - 1;
+                return Err(SyntaxError::confusing_unicode(self.line, col, offset, ch));
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the unconsumed tail of the buffered line, starting at the
+    /// cursor.
+    fn peek_str(&self) -> &str {
+        &self.buf_norm[self.col - 1..]
+    }
+
+    /// Returns the next unconsumed char without advancing the cursor, or
+    /// `None` if the buffered line has been fully consumed.
+    fn peek(&self) -> Option<char> {
+        self.peek_str().chars().next()
+    }
+
+    /// Returns the `n`th unconsumed char (0-indexed) without advancing the
+    /// cursor, or `None` if there aren't that many left in the buffered
+    /// line.
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.peek_str().chars().nth(n)
+    }
+
+    /// Consumes and returns the next unconsumed char, advancing the cursor
+    /// past it by its UTF-8 width. Returns `None` without advancing at the
+    /// end of the buffered line.
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.col += ch.len_utf8();
+        Some(ch)
+    }
+}
+
+/// Lexes an entire reader into a `Vec<Token>`, including the trailing
+/// `Token::Eof`.
+///
+/// This is a convenience for tooling (parsers, formatters, IDE integration)
+/// that wants all of a file's tokens up front rather than pulling them one
+/// at a time from the `Lexer` iterator. Stops at, and returns, the first
+/// `Token::Err` encountered, rather than collecting it into the `Vec`.
+pub fn lex<'ns, B: BufRead>(reader: B, ns: &'ns NameSpace) -> Result<Vec<Token<'ns>>> {
+    let mut toks = Vec::new();
+    for tok in Lexer::new(reader, ns) {
+        match tok {
+            Token::Err(e) => return Err(e),
+            tok => toks.push(tok),
+        }
+    }
+    Ok(toks)
+}
+
+/// Returns the column and value of the first bidirectional-control
+/// codepoint in `line`, if any.
+///
+/// Covers the embedding/override controls (U+202A-U+202E) and the isolate
+/// controls (U+2066-U+2069), the codepoints used by "Trojan Source" attacks
+/// to make source text display in an order different from how it lexes.
+fn find_bidi_control(line: &str) -> Option<(usize, char)> {
+    let mut col = 1;
+    for ch in line.chars() {
+        let cp = ch as u32;
+        if (0x202A <= cp && cp <= 0x202E) || (0x2066 <= cp && cp <= 0x2069) {
+            return Some((col, ch));
+        }
+        col += ch.len_utf8();
+    }
+    None
+}
+
+/// Decodes a single escape sequence, given the text immediately following
+/// the backslash.
+///
+/// Supports the simple single-character escapes (`\n`, `\r`, `\t`, `\\`,
+/// and otherwise the escaped character verbatim), `\xHH\` hex escapes,
+/// `\ooo\` octal escapes, and a backslash followed directly by a newline as
+/// a line continuation.
+///
+/// Returns the decoded character, or `None` for a line continuation, which
+/// contributes nothing to the token. The second member is the number of
+/// bytes of `after_backslash` consumed (not counting the backslash itself).
+/// Shared by `lex_quote` and `lex_zero`'s `0'c` character-code literal, the
+/// only two places an escape can appear.
+fn decode_escape(after_backslash: &str) -> (Option<char>, usize) {
+    match after_backslash.chars().next() {
+        None => (None, 0),
+        Some('\n') => (None, 1),
+        Some('n') => (Some('\n'), 1),
+        Some('r') => (Some('\r'), 1),
+        Some('t') => (Some('\t'), 1),
+        Some('\\') => (Some('\\'), 1),
+        Some('x') => {
+            let (ch, len) = decode_numeric_escape(&after_backslash[1..], 16);
+            (ch, len + 1)
+        }
+        Some(ch) if ch.is_digit(8) => decode_numeric_escape(after_backslash, 8),
+        Some(ch) => (Some(ch), ch.len_utf8()),
+    }
+}
+
+/// Decodes the digits of a `\xHH\` or `\ooo\` numeric escape, given the text
+/// starting at the first digit (i.e. past the leading `x`, if any).
+///
+/// Consumes a trailing `\` terminator when one follows the digits, per ISO
+/// quoted-token grammar.
+fn decode_numeric_escape(digits: &str, radix: u32) -> (Option<char>, usize) {
+    let len: usize = digits.chars().take_while(|ch| ch.is_digit(radix)).map(|ch| ch.len_utf8()).sum();
+    let code = u32::from_str_radix(&digits[..len], radix).unwrap_or(0);
+    let ch = ::std::char::from_u32(code).unwrap_or('\u{FFFD}');
+    let mut consumed = len;
+    if digits[len..].starts_with('\\') {
+        consumed += 1;
+    }
+    (Some(ch), consumed)
 }
 
 impl<'ns, B: BufRead> Iterator for Lexer<'ns, B> {
@@ -105,26 +302,37 @@ impl<'ns, B: BufRead> Iterator for Lexer<'ns, B> {
 
     /// Extracts the next token from the underlying reader.
     fn next(&mut self) -> Option<Token<'ns>> {
-        // Refill the buffers.
-        if self.buf_norm.len() <= self.col {
-            self.line += 1;
-            self.col = 1;
-            self.buf_line.clear();
-            match self.reader.read_line(&mut self.buf_line) {
-                Ok(0) => return None, // Nothing more to read
-                Ok(_) => (),          // The buffer is refilled successfully
-                Err(e) => return Some(Token::Err(SyntaxError::wrap(self.line, self.col, e))),
+        match self.ensure_input() {
+            Ok(true) => (),
+            Ok(false) => {
+                if self.eof_emitted {
+                    return None;
+                }
+                self.eof_emitted = true;
+                return Some(Token::Eof(self.line, self.col, self.offset()));
             }
+            Err(e) => return Some(Token::Err(e)),
+        }
 
-            // Perform Unicode normalization.
-            // This has security, usability, and performance implications.
-            self.buf_norm.clear();
-            self.buf_norm.extend(self.buf_line.nfkc());
+        // Quotes and block comments may span multiple lines, so unlike
+        // every other token they can't be lexed from the cursor alone:
+        // they need to pull further lines from `self.reader` directly, via
+        // `self.ensure_input`. Handle them here, before `self.lex` dispatches
+        // on a single peeked char.
+        match self.peek().unwrap() {
+            '\'' | '\"' => return Some(self.lex_quote()),
+            _ => (),
+        }
+        if self.peek_str().starts_with("/*") {
+            let tok = self.lex_block_comment();
+            return match tok {
+                Token::Comment(..) if self.skip_space => self.next(),
+                _ => Some(tok),
+            };
         }
 
         // Lex the next token.
-        let (tok, len) = self.lex(&self.buf_norm[self.col - 1..]);
-        self.col += len;
+        let tok = self.lex();
 
         // Skip space and comment tokens.
         match tok {
@@ -141,6 +349,7 @@ impl<'ns> Token<'ns> {
     pub fn line(&self) -> usize {
         match *self {
             Token::Err(ref err) => err.line(),
+            Token::Eof(line, ..) => line,
             Token::Funct(line, ..) => line,
             Token::Str(line, ..) => line,
             Token::Var(line, ..) => line,
@@ -165,6 +374,7 @@ impl<'ns> Token<'ns> {
     pub fn col(&self) -> usize {
         match *self {
             Token::Err(ref err) => err.col(),
+            Token::Eof(_, col, ..) => col,
             Token::Funct(_, col, ..) => col,
             Token::Str(_, col, ..) => col,
             Token::Var(_, col, ..) => col,
@@ -183,12 +393,38 @@ impl<'ns> Token<'ns> {
             Token::Comment(_, col, ..) => col,
         }
     }
+
+    /// Returns the byte offset into the source text of the start of the token.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        match *self {
+            Token::Err(ref err) => err.span().start,
+            Token::Eof(_, _, offset) => offset,
+            Token::Funct(_, _, offset, ..) => offset,
+            Token::Str(_, _, offset, ..) => offset,
+            Token::Var(_, _, offset, ..) => offset,
+            Token::Int(_, _, offset, ..) => offset,
+            Token::Float(_, _, offset, ..) => offset,
+            Token::ParenOpen(_, _, offset) => offset,
+            Token::ParenClose(_, _, offset) => offset,
+            Token::BracketOpen(_, _, offset) => offset,
+            Token::BracketClose(_, _, offset) => offset,
+            Token::BraceOpen(_, _, offset) => offset,
+            Token::BraceClose(_, _, offset) => offset,
+            Token::Bar(_, _, offset, ..) => offset,
+            Token::Comma(_, _, offset, ..) => offset,
+            Token::Dot(_, _, offset) => offset,
+            Token::Space(_, _, offset, ..) => offset,
+            Token::Comment(_, _, offset, ..) => offset,
+        }
+    }
 }
 
 impl<'ns> fmt::Display for Token<'ns> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Token::Err(ref err) => write!(f, "{}", err),
+            Token::Eof(..) => f.write_str("EOF"),
             Token::Funct(.., val) => write!(f, "{}", val),
             Token::Str(.., val) => write!(f, "{}", val),
             Token::Var(.., val) => write!(f, "{}", val),
@@ -203,10 +439,8 @@ impl<'ns> fmt::Display for Token<'ns> {
             Token::Bar(..) => f.write_str("|"),
             Token::Comma(..) => f.write_str(","),
             Token::Dot(..) => f.write_str("."),
-
-            // TODO: Space and Comment should report their content.
-            Token::Space(..) => f.write_str("SPACE"),
-            Token::Comment(..) => f.write_str("COMMENT"),
+            Token::Space(.., val) => write!(f, "{}", val),
+            Token::Comment(.., val) => write!(f, "{}", val),
         }
     }
 }
@@ -216,28 +450,30 @@ impl<'ns> fmt::Display for Token<'ns> {
 
 impl<'ns, B: BufRead> Lexer<'ns, B> {
     /// The main switch of the lexer.
-    fn lex(&self, line: &str) -> (Token<'ns>, usize) {
-        match line.chars().nth(0).unwrap() {
-            '(' => self.lex_simple(line),
-            ')' => self.lex_simple(line),
-            '[' => self.lex_simple(line),
-            ']' => self.lex_simple(line),
-            '{' => self.lex_simple(line),
-            '}' => self.lex_simple(line),
-            ',' => self.lex_simple(line),
-            '|' => self.lex_simple(line),
-            '.' => self.lex_simple(line),
-            '%' => self.lex_comment(line),
-            '_' => self.lex_var(line),
-            '\'' => self.lex_quote(line),
-            '\"' => self.lex_quote(line),
-            '-' => self.lex_minus(line),
-            '0' => self.lex_zero(line),
-            ch if ch.is_digit(10) => self.lex_decimal(line),
-            ch if ch.is_whitespace() => self.lex_space(line),
-            ch if ch.is_control() => self.lex_space(line),
-            ch if ch.is_uppercase() => self.lex_var(line),
-            _ => self.lex_functor(line),
+    ///
+    /// Dispatches on the peeked char alone; every arm advances the cursor
+    /// itself as it builds its token, so no line slice or length bookkeeping
+    /// is threaded through the call.
+    fn lex(&mut self) -> Token<'ns> {
+        match self.peek().unwrap() {
+            '(' => self.lex_simple(),
+            ')' => self.lex_simple(),
+            '[' => self.lex_simple(),
+            ']' => self.lex_simple(),
+            '{' => self.lex_simple(),
+            '}' => self.lex_simple(),
+            ',' => self.lex_simple(),
+            '|' => self.lex_simple(),
+            '.' => self.lex_simple(),
+            '%' => self.lex_comment(),
+            '_' => self.lex_var(),
+            '-' => self.lex_minus(),
+            '0' => self.lex_zero(),
+            ch if ch.is_digit(10) => self.lex_decimal(),
+            ch if ch.is_whitespace() => self.lex_space(),
+            ch if ch.is_control() => self.lex_space(),
+            ch if ch.is_uppercase() => self.lex_var(),
+            _ => self.lex_functor(),
         }
     }
 
@@ -250,19 +486,25 @@ impl<'ns, B: BufRead> Lexer<'ns, B> {
     /// Commas, periods, and pipes are not allowed within other function
     /// symbols.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_functor(&self, line: &str) -> (Token<'ns>, usize) {
-        lazy_static! {
-            static ref RE: Regex = {
-                let pattern = r"^([\w\d]+|[\p{S}\p{Pc}\p{Pd}\p{Po}]+)";
-                Regex::new(pattern).unwrap()
-            };
+    /// The cursor MUST be at the start of the token.
+    fn lex_functor(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        let word_mode = is_word_char(self.peek().unwrap());
+
+        loop {
+            match self.peek() {
+                Some(ch) if ch == ',' || ch == '.' || ch == '|' => break,
+                Some(ch) if is_functor_char(word_mode, ch) => {
+                    self.bump();
+                }
+                _ => break,
+            }
         }
 
-        let m = RE.find(line).unwrap();
-        let s = m.as_str().split(|ch| ch == ',' || ch == '.' || ch == '|').nth(0).unwrap();
-        let tok = Token::Funct(self.line(), self.col(), self.ns.name(s));
-        (tok, s.len())
+        let name = self.ns.name(&self.buf_norm[col - 1..self.col - 1]);
+        Token::Funct(line, col, offset, name)
     }
 
     /// Returns the token for a variable term.
@@ -270,50 +512,54 @@ impl<'ns, B: BufRead> Lexer<'ns, B> {
     /// Variables start with a capital letter or underscore and are composed
     /// only of letters and underscores.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_var(&self, line: &str) -> (Token<'ns>, usize) {
-        lazy_static! {
-            static ref RE: Regex = {
-                let pattern = r"^[\p{Lu}_][\w\d]*";
-                Regex::new(pattern).unwrap()
-            };
+    /// The cursor MUST be at the start of the token.
+    fn lex_var(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        self.bump(); // the leading capital letter or underscore
+
+        while let Some(ch) = self.peek() {
+            if is_word_char(ch) {
+                self.bump();
+            } else {
+                break;
+            }
         }
 
-        let m = RE.find(line).unwrap();
-        let s = m.as_str();
-        let tok = Token::Var(self.line(), self.col(), self.ns.name(s));
-        (tok, s.len())
+        let name = self.ns.name(&self.buf_norm[col - 1..self.col - 1]);
+        Token::Var(line, col, offset, name)
     }
 
     /// Returns the token for a symbol starting with a minus.
     ///
     /// A minus can start both numeric and function symbol tokens.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_minus(&self, line: &str) -> (Token<'ns>, usize) {
-        let mut len = 0;
-        let tok = match line.chars().nth(1) {
+    /// The cursor MUST be at the start of the token.
+    fn lex_minus(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        match self.peek_at(1) {
             Some('0') => {
-                let (subtok, sublen) = self.lex_zero(&line[1..]);
-                len += 1 + sublen;
-                match subtok {
-                    Token::Int(_, _, val) => Token::Int(self.line(), self.col(), -val),
-                    Token::Float(_, _, val) => Token::Float(self.line(), self.col(), -val),
+                self.bump();
+                match self.lex_zero() {
+                    Token::Int(_, _, _, val) => Token::Int(line, col, offset, -val),
+                    Token::Float(_, _, _, val) => Token::Float(line, col, offset, -val),
+                    err @ Token::Err(_) => err,
                     _ => unreachable!("lex_zero must return a numeric token"),
                 }
             }
             Some(ch) if ch.is_digit(10) => {
-                let (subtok, sublen) = self.lex_decimal(&line[1..]);
-                len += 1 + sublen;
-                match subtok {
-                    Token::Int(_, _, val) => Token::Int(self.line(), self.col(), -val),
-                    Token::Float(_, _, val) => Token::Float(self.line(), self.col(), -val),
+                self.bump();
+                match self.lex_decimal() {
+                    Token::Int(_, _, _, val) => Token::Int(line, col, offset, -val),
+                    Token::Float(_, _, _, val) => Token::Float(line, col, offset, -val),
                     _ => unreachable!("lex_zero must return a numeric token"),
                 }
             }
-            _ => return self.lex_functor(line),
-        };
-        (tok, len)
+            _ => self.lex_functor(),
+        }
     }
 
     /// Returns the token for a number with a leading zero.
@@ -322,44 +568,164 @@ impl<'ns, B: BufRead> Lexer<'ns, B> {
     /// - 'x' for hexadecimal
     /// - 'o' for octal
     /// - 'b' for binary
+    /// - '\'' for an ISO character-code literal, see `self.lex_char_code`
     /// - otherwise decimal is assumed
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_zero(&self, line: &str) -> (Token<'ns>, usize) {
-        let mut len = 0;
+    /// The cursor MUST be at the start of the token.
+    fn lex_zero(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
 
         // We know the first char is '0'. The second char gives the radix.
         // If base 10, jump to `self.lex_decimal`.
         let radix: u32;
-        match line.chars().nth(1) {
-            Some('x') => radix = 16,
+        match self.peek_at(1) {
+            Some('\'') => return self.lex_char_code(),
+            Some('x') => {
+                self.bump(); // '0'
+                self.bump(); // 'x'
+                return self.lex_hex_number(line, col, offset);
+            }
             Some('o') => radix = 8,
             Some('b') => radix = 2,
-            Some('.') => return self.lex_decimal(line),
-            Some(ch) if ch.is_digit(10) => return self.lex_decimal(line),
-            _ => return (Token::Int(self.line(), self.col(), 0), 1),
+            Some('.') => return self.lex_decimal(),
+            Some(ch) if ch.is_digit(10) => return self.lex_decimal(),
+            _ => {
+                self.bump();
+                return Token::Int(line, col, offset, 0);
+            }
         }
-        len += 2;
+        self.bump(); // '0'
+        self.bump(); // the radix marker
 
         // Buffer up all chars in the given radix.
         let mut buf = String::with_capacity(32);
         buf.push('0');
-        for ch in line.chars().skip(2) {
-            match ch {
-                ch if ch.is_digit(radix) => {
-                    len += ch.len_utf8();
-                    buf.push(ch);
-                }
-                _ => break,
+        while let Some(ch) = self.peek() {
+            if ch.is_digit(radix) {
+                buf.push(ch);
+                self.bump();
+            } else {
+                break;
             }
         }
 
         // Parse the buffer into an integer.
-        let tok = match i64::from_str_radix(buf.as_str(), radix) {
-            Ok(x) => Token::Int(self.line(), self.col(), x),
+        match i64::from_str_radix(buf.as_str(), radix) {
+            Ok(x) => Token::Int(line, col, offset, x),
             Err(_) => unreachable!("the buffer must be valid in the given radix"),
+        }
+    }
+
+    /// Returns the token for a hexadecimal number whose `0x` prefix has
+    /// already been consumed by the caller: either a plain hex integer
+    /// (`0x2A`) or, when a radix point or binary exponent follows, a
+    /// C99-style hex float like `0x1.8p3` (mantissa in hex, exponent in
+    /// decimal, value is `mantissa * 2^exponent`). The `p`/`P` exponent is
+    /// mandatory whenever a radix point is present, since the clause
+    /// terminator is also a bare `.`; without a required exponent to
+    /// disambiguate, `0x1.8` would be read as the end of a hex integer
+    /// clause followed by a stray `8`.
+    ///
+    /// The cursor must be positioned just after the `0x`.
+    fn lex_hex_number(&mut self, line: usize, col: usize, offset: usize) -> Token<'ns> {
+        let mut int_part = String::with_capacity(16);
+        while let Some(ch) = self.peek() {
+            if ch.is_digit(16) {
+                int_part.push(ch);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        let mut frac_part = String::new();
+        let mut is_float = false;
+        if self.peek() == Some('.') && self.peek_at(1).map_or(false, |ch| ch.is_digit(16)) {
+            is_float = true;
+            self.bump(); // '.'
+            while let Some(ch) = self.peek() {
+                if ch.is_digit(16) {
+                    frac_part.push(ch);
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+        }
+
+        if !is_float && int_part.is_empty() {
+            // No digits and no radix point followed the `0x`: same as the
+            // other radix markers when no digits follow, this is just `0`.
+            return Token::Int(line, col, offset, 0);
+        }
+
+        match self.peek() {
+            Some('p') | Some('P') => {
+                self.bump();
+            }
+            _ => {
+                if is_float {
+                    return Token::Err(SyntaxError::unexpected(line, col, offset, "hex exponent"));
+                }
+                return match i64::from_str_radix(&int_part, 16) {
+                    Ok(x) => Token::Int(line, col, offset, x),
+                    Err(_) => unreachable!("the buffer must be valid hex"),
+                };
+            }
+        }
+
+        let neg_exp = self.peek() == Some('-');
+        if neg_exp || self.peek() == Some('+') {
+            self.bump();
+        }
+        let mut exp_buf = String::with_capacity(4);
+        while let Some(ch) = self.peek() {
+            if ch.is_digit(10) {
+                exp_buf.push(ch);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        if exp_buf.is_empty() {
+            return Token::Err(SyntaxError::unexpected(line, col, offset, "hex exponent"));
+        }
+        let exponent: i32 = exp_buf.parse().unwrap();
+        let exponent = if neg_exp { -exponent } else { exponent };
+
+        Token::Float(line, col, offset, hex_mantissa_to_f64(&int_part, &frac_part, exponent))
+    }
+
+    /// Returns the token for an ISO `0'c` character-code literal, whose
+    /// value is the codepoint of the character immediately following the
+    /// quote, with the same escape sequences as a quoted token (so `0'\n`
+    /// is 10 and `0'\\` is 92).
+    ///
+    /// The cursor MUST be at the start of the token, i.e. on the leading
+    /// `'0'` of `0'c`.
+    fn lex_char_code(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        self.col += 2; // consume the "0'"
+
+        let ch = match self.peek() {
+            Some('\\') => {
+                self.col += 1;
+                let (decoded, len) = decode_escape(self.peek_str());
+                self.col += len;
+                decoded.unwrap_or('\0')
+            }
+            Some(ch) => {
+                self.col += ch.len_utf8();
+                ch
+            }
+            None => '\0',
         };
-        (tok, len)
+
+        Token::Int(line, col, offset, ch as i64)
     }
 
     /// Returns the token for a decimal number.
@@ -369,125 +735,301 @@ impl<'ns, B: BufRead> Lexer<'ns, B> {
     ///
     /// This routine does not handle leading signs. See `lex_minus`.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_decimal(&self, line: &str) -> (Token<'ns>, usize) {
-        lazy_static! {
-            static ref RE: Regex = {
-                let pattern = r"^\d[\d_]*(\.[\d_]+)?(e-?[\d_]+)?";
-                Regex::new(pattern).unwrap()
-            };
+    /// The cursor MUST be at the start of the token.
+    fn lex_decimal(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        let mut s = String::with_capacity(16);
+        let mut is_float = false;
+
+        self.lex_digits(&mut s);
+
+        if self.peek() == Some('.') && self.peek_at(1).map_or(false, is_decimal_digit) {
+            is_float = true;
+            s.push('.');
+            self.bump();
+            self.lex_digits(&mut s);
         }
 
-        let m = RE.find(line).unwrap();
-        let s = m.as_str();
-        let float = s.chars().any(|ch| ch == 'e' || ch == '.');
-        let tok = match float {
-            true => Token::Float(self.line(), self.col(), s.parse().unwrap()),
-            false => Token::Int(self.line(), self.col(), s.parse().unwrap()),
-        };
-        (tok, s.len())
+        if self.peek() == Some('e') {
+            let has_sign = self.peek_at(1) == Some('-');
+            let after_e = if has_sign { 2 } else { 1 };
+            if self.peek_at(after_e).map_or(false, is_decimal_digit) {
+                is_float = true;
+                s.push('e');
+                self.bump();
+                if has_sign {
+                    s.push('-');
+                    self.bump();
+                }
+                self.lex_digits(&mut s);
+            }
+        }
+
+        match is_float {
+            true => Token::Float(line, col, offset, s.parse().unwrap()),
+            false => Token::Int(line, col, offset, s.parse().unwrap()),
+        }
+    }
+
+    /// Appends a run of digits and underscores from the cursor into `buf`,
+    /// advancing past them. A helper for `lex_decimal`'s three digit runs
+    /// (integer part, fractional part, exponent).
+    fn lex_digits(&mut self, buf: &mut String) {
+        while let Some(ch) = self.peek() {
+            if is_decimal_digit(ch) {
+                buf.push(ch);
+                self.bump();
+            } else {
+                break;
+            }
+        }
     }
 
     /// Returns a token for a function symbol or string enclosed in quotes.
     ///
-    /// Escape sequences are replaced and the token will not include the
-    /// surrounding quotes. An error is returned if the quote is unclosed.
+    /// Escape sequences (including `\xHH\` hex escapes, `\ooo\` octal
+    /// escapes, and a backslash-newline line continuation, decoded by the
+    /// shared `decode_escape`) are replaced and the token will not include
+    /// the surrounding quotes. An error is returned if the quote is
+    /// unclosed.
+    ///
+    /// Quoted content may span multiple lines: this pulls further lines
+    /// from `self.reader` via `self.ensure_input` whenever it runs past the
+    /// end of the current line, continuing to scan until it finds the
+    /// closing quote or the reader is exhausted.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_quote(&self, line: &str) -> (Token<'ns>, usize) {
-        let quote = line.chars().nth(0).unwrap();
+    /// Called only when the current position is a quote character.
+    fn lex_quote(&mut self) -> Token<'ns> {
+        let start_line = self.line;
+        let start_col = self.col;
+        let start_offset = self.offset();
+        let quote = self.bump().unwrap();
+
         let mut buf = String::with_capacity(32);
-        let mut escape = false;
-        let mut ok = false;
-        for ch in line.chars().skip(1) {
-            if escape {
-                match ch {
-                    'n' => buf.push('\n'),
-                    'r' => buf.push('\r'),
-                    't' => buf.push('\t'),
-                    '\\' => buf.push('\\'),
-                    ch => buf.push(ch),
+        loop {
+            match self.ensure_input() {
+                Ok(true) => (),
+                Ok(false) => {
+                    return Token::Err(SyntaxError::unbalanced(start_line, start_col, start_offset, quote))
                 }
-                escape = false;
-            } else {
-                match ch {
-                    '\\' => escape = true,
-                    ch if ch == quote => {
-                        ok = true;
-                        break;
-                    }
-                    ch => buf.push(ch),
+                Err(e) => return Token::Err(e),
+            }
+
+            let ch = self.peek().unwrap();
+            if ch == '\\' {
+                self.col += 1;
+                let (decoded, len) = decode_escape(self.peek_str());
+                self.col += len;
+                if let Some(ch) = decoded {
+                    buf.push(ch);
                 }
+            } else if ch == quote {
+                self.bump();
+                break;
+            } else {
+                self.bump();
+                buf.push(ch);
             }
         }
 
-        let len = buf.len() + 2;
-        let tok = match ok {
-            true if quote == '\"' => Token::Str(self.line(), self.col(), self.ns.name(buf)),
-            true => Token::Funct(self.line(), self.col(), self.ns.name(buf)),
-            false => Token::Err(SyntaxError::unbalanced(self.line(), self.col(), quote)),
-        };
-        (tok, len)
+        match quote {
+            '\"' => Token::Str(start_line, start_col, start_offset, self.ns.name(buf)),
+            _ => Token::Funct(start_line, start_col, start_offset, self.ns.name(buf)),
+        }
     }
 
     /// Returns the token for a single char symbol.
     ///
     /// These include the various parens as well as the comma, bar, and period.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_simple(&self, line: &str) -> (Token<'ns>, usize) {
-        let tok = match line.chars().nth(0).unwrap() {
-            '(' => Token::ParenOpen(self.line(), self.col()),
-            ')' => Token::ParenClose(self.line(), self.col()),
-            '[' => Token::BracketOpen(self.line(), self.col()),
-            ']' => Token::BracketClose(self.line(), self.col()),
-            '{' => Token::BraceOpen(self.line(), self.col()),
-            '}' => Token::BraceClose(self.line(), self.col()),
-            ',' => Token::Comma(self.line(), self.col(), self.ns.name(",")),
-            '|' => Token::Bar(self.line(), self.col(), self.ns.name("|")),
-            '.' => Token::Dot(self.line(), self.col()),
+    /// The cursor MUST be at the start of the token.
+    fn lex_simple(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        let ch = self.bump().unwrap();
+        match ch {
+            '(' => Token::ParenOpen(line, col, offset),
+            ')' => Token::ParenClose(line, col, offset),
+            '[' => Token::BracketOpen(line, col, offset),
+            ']' => Token::BracketClose(line, col, offset),
+            '{' => Token::BraceOpen(line, col, offset),
+            '}' => Token::BraceClose(line, col, offset),
+            ',' => Token::Comma(line, col, offset, self.ns.name(",")),
+            '|' => Token::Bar(line, col, offset, self.ns.name("|")),
+            '.' => Token::Dot(line, col, offset),
             _ => unreachable!("lex_simple must be called with a simple character"),
-        };
-        (tok, 1)
+        }
     }
 
     /// Returns the next whitespace token.
     ///
-    /// This includes characters in the unicode Whitespace and Other
-    /// categories, including control characters.
+    /// This includes whitespace and control characters.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_space(&self, line: &str) -> (Token<'ns>, usize) {
-        lazy_static! {
-            static ref RE: Regex = {
-                let pattern = r"^[\s\p{C}]+";
-                Regex::new(pattern).unwrap()
-            };
+    /// The cursor MUST be at the start of the token.
+    fn lex_space(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        while let Some(ch) = self.peek() {
+            if ch.is_whitespace() || ch.is_control() {
+                self.bump();
+            } else {
+                break;
+            }
         }
+        let name = self.ns.name(&self.buf_norm[col - 1..self.col - 1]);
+        Token::Space(line, col, offset, name)
+    }
+
+    /// Lexes a `/* ... */` block comment, which may span multiple lines.
+    ///
+    /// Comments may nest: each `/*` increases a depth counter and each `*/`
+    /// decreases it, with the comment ending only once the depth returns to
+    /// zero. If the reader is exhausted first, returns `Token::Err` with a
+    /// `SyntaxError::unterminated_comment` pointing at the opening `/*`
+    /// rather than at EOF.
+    ///
+    /// Called only when the current position starts with `/*`. Unlike the
+    /// other `lex_*` methods, this one takes `&mut self` and pulls further
+    /// input via `self.ensure_input` as needed, since the comment it lexes
+    /// isn't bound to a single line. The token's interned text is built up
+    /// as the comment is scanned, rather than sliced from `buf_norm`
+    /// afterward, since a multi-line comment's body doesn't survive in a
+    /// single buffered line the way other tokens' do.
+    fn lex_block_comment(&mut self) -> Token<'ns> {
+        let start_line = self.line;
+        let start_col = self.col;
+        let start_offset = self.offset();
+        self.col += 2; // consume the opening "/*"
+        let mut depth = 1;
+        let mut buf = String::with_capacity(32);
+
+        while depth > 0 {
+            match self.ensure_input() {
+                Ok(true) => (),
+                Ok(false) => {
+                    return Token::Err(SyntaxError::unterminated_comment(start_line, start_col, start_offset))
+                }
+                Err(e) => return Token::Err(e),
+            }
 
-        let m = RE.find(line).unwrap();
-        let s = m.as_str();
-        let tok = Token::Space(self.line(), self.col());
-        (tok, s.len())
+            if self.peek_str().starts_with("/*") {
+                depth += 1;
+                buf.push_str("/*");
+                self.col += 2;
+            } else if self.peek_str().starts_with("*/") {
+                depth -= 1;
+                if depth > 0 {
+                    buf.push_str("*/");
+                }
+                self.col += 2;
+            } else {
+                let ch = self.peek().unwrap();
+                buf.push(ch);
+                self.bump();
+            }
+        }
+
+        Token::Comment(start_line, start_col, start_offset, self.ns.name(buf))
     }
 
     /// Retuns a token for a comment.
     ///
     /// Comments start with '%' and extend to the end of the line.
     ///
-    /// The token MUST be at the start of the line.
-    fn lex_comment(&self, line: &str) -> (Token<'ns>, usize) {
-        lazy_static! {
-            static ref RE: Regex = {
-                let pattern = r"^%.*";
-                Regex::new(pattern).unwrap()
-            };
+    /// The cursor MUST be at the start of the token.
+    fn lex_comment(&mut self) -> Token<'ns> {
+        let line = self.line();
+        let col = self.col();
+        let offset = self.offset();
+        while let Some(ch) = self.peek() {
+            if ch == '\n' {
+                break;
+            }
+            self.bump();
         }
+        let name = self.ns.name(&self.buf_norm[col - 1..self.col - 1]);
+        Token::Comment(line, col, offset, name)
+    }
+}
+
+/// Returns whether `ch` is part of an alphanumeric "word" token (a variable
+/// or a word-style functor), mirroring the old regex lexer's `\w` class:
+/// Unicode alphanumerics plus underscore.
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Returns whether `ch` is a decimal digit or the underscore digit-group
+/// separator permitted inside numeric literals.
+fn is_decimal_digit(ch: char) -> bool {
+    ch.is_digit(10) || ch == '_'
+}
+
+/// Converts a hex float's mantissa digits and binary exponent into an `f64`,
+/// computing `mantissa * 2^exponent` with correct rounding even when
+/// `int_part`/`frac_part` together carry more than the 53 bits of precision
+/// an `f64` can hold.
+///
+/// `int_part` and `frac_part` are the hex digits before and after the radix
+/// point (either may be empty, but not both); `exponent` is the decimal
+/// value of the `p`/`P` exponent, already adjusted for sign.
+fn hex_mantissa_to_f64(int_part: &str, frac_part: &str, exponent: i32) -> f64 {
+    let mut digits: Vec<u32> = Vec::with_capacity(int_part.len() + frac_part.len());
+    digits.extend(int_part.chars().map(|ch| ch.to_digit(16).unwrap()));
+    digits.extend(frac_part.chars().map(|ch| ch.to_digit(16).unwrap()));
+
+    // Each hex digit after the radix point shifts the value down 4 bits.
+    let mut exp2 = exponent - 4 * frac_part.len() as i32;
 
-        let m = RE.find(line).unwrap();
-        let s = m.as_str();
-        let tok = Token::Space(self.line(), self.col());
-        (tok, s.len())
+    // Leading zero digits carry no value; dropping them keeps the 64-bit
+    // mantissa window below filled with significant bits.
+    let start = digits.iter().position(|&d| d != 0).unwrap_or(digits.len());
+    let digits = &digits[start..];
+    if digits.is_empty() {
+        return 0.0;
+    }
+
+    // Accumulate up to 64 significant bits into `mantissa`; `u64 as f64`
+    // already rounds to nearest, ties-to-even, so no manual rounding is
+    // needed for the bits that fit. Digits beyond that window only need to
+    // set a sticky bit so a true value that isn't an exact tie doesn't get
+    // rounded as if it were one.
+    let mut mantissa: u64 = 0;
+    let mut bits = 0u32;
+    let mut i = 0;
+    while i < digits.len() && bits <= 60 {
+        mantissa = (mantissa << 4) | digits[i] as u64;
+        bits += 4;
+        i += 1;
+    }
+    exp2 += (digits.len() - i) as i32 * 4;
+
+    if digits[i..].iter().any(|&d| d != 0) {
+        mantissa |= 1;
+    }
+
+    (mantissa as f64) * 2f64.powi(exp2)
+}
+
+/// Returns whether `ch` may extend a functor symbol already in progress.
+///
+/// `word_mode` functors continue with further word chars; symbol-style
+/// functors (composed of punctuation/symbol characters, e.g. `:-` or `+++`)
+/// continue with anything that's neither alphanumeric, whitespace, a
+/// structural delimiter, nor a control character — approximating the old
+/// regex lexer's `\p{S}\p{Pc}\p{Pd}\p{Po}` union of categories.
+fn is_functor_char(word_mode: bool, ch: char) -> bool {
+    if word_mode {
+        is_word_char(ch)
+    } else {
+        match ch {
+            '(' | ')' | '[' | ']' | '{' | '}' => false,
+            ch => !ch.is_alphanumeric() && !ch.is_whitespace() && !ch.is_control(),
+        }
     }
 }
 
@@ -509,26 +1051,27 @@ mod test {
                   \t\t   \t\n";
         let ns = NameSpace::new();
         let mut toks = Lexer::new(pl.as_bytes(), &ns);
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 1, ns.name("_abcd")));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 7, ns.name("ABCD")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 12, ns.name("foobar")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 19, ns.name("hello world")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 33, ns.name("+++")));
-        assert_eq!(toks.next().unwrap(), Token::Int(3, 1, 123));
-        assert_eq!(toks.next().unwrap(), Token::Float(3, 5, 456.789));
-        assert_eq!(toks.next().unwrap(), Token::Float(3, 13, 8.765e43));
-        assert_eq!(toks.next().unwrap(), Token::Float(3, 22, 1e-1));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 1, 0xDEADBEEF));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 12, 0o644));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 18, 0b11001100));
-        assert_eq!(toks.next().unwrap(), Token::Int(4, 29, 0987654321));
-        assert_eq!(toks.next().unwrap(), Token::Float(4, 40, 0.123));
-        assert_eq!(toks.next().unwrap(), Token::Funct(5, 1, ns.name("->")));
-        assert_eq!(toks.next().unwrap(), Token::Int(5, 4, -0xff));
-        assert_eq!(toks.next().unwrap(), Token::Float(5, 10, -1.23));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(5, 16));
-        assert_eq!(toks.next().unwrap(), Token::Funct(5, 17, ns.name("-")));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(5, 18));
+        assert_eq!(toks.next().unwrap(), Token::Var(1, 1, 0, ns.name("_abcd")));
+        assert_eq!(toks.next().unwrap(), Token::Var(1, 7, 6, ns.name("ABCD")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 12, 11, ns.name("foobar")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 19, 18, ns.name("hello world")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 33, 32, ns.name("+++")));
+        assert_eq!(toks.next().unwrap(), Token::Int(3, 1, 56, 123));
+        assert_eq!(toks.next().unwrap(), Token::Float(3, 5, 60, 456.789));
+        assert_eq!(toks.next().unwrap(), Token::Float(3, 13, 68, 8.765e43));
+        assert_eq!(toks.next().unwrap(), Token::Float(3, 22, 77, 1e-1));
+        assert_eq!(toks.next().unwrap(), Token::Int(4, 1, 82, 0xDEADBEEF));
+        assert_eq!(toks.next().unwrap(), Token::Int(4, 12, 93, 0o644));
+        assert_eq!(toks.next().unwrap(), Token::Int(4, 18, 99, 0b11001100));
+        assert_eq!(toks.next().unwrap(), Token::Int(4, 29, 110, 0987654321));
+        assert_eq!(toks.next().unwrap(), Token::Float(4, 40, 121, 0.123));
+        assert_eq!(toks.next().unwrap(), Token::Funct(5, 1, 127, ns.name("->")));
+        assert_eq!(toks.next().unwrap(), Token::Int(5, 4, 130, -0xff));
+        assert_eq!(toks.next().unwrap(), Token::Float(5, 10, 136, -1.23));
+        assert_eq!(toks.next().unwrap(), Token::ParenOpen(5, 16, 142));
+        assert_eq!(toks.next().unwrap(), Token::Funct(5, 17, 143, ns.name("-")));
+        assert_eq!(toks.next().unwrap(), Token::ParenClose(5, 18, 144));
+        assert_eq!(toks.next().unwrap(), Token::Eof(7, 1, 153));
         assert!(toks.next().is_none());
     }
 
@@ -541,38 +1084,238 @@ mod test {
         let mut toks = Lexer::new(pl.as_bytes(), &ns);
 
         // member(H, [H|T]).
-        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, ns.name("member")));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(1, 7));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 8, ns.name("H")));
-        assert_eq!(toks.next().unwrap(), Token::Comma(1, 9, ns.name(",")));
-        assert_eq!(toks.next().unwrap(), Token::BracketOpen(1, 11));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 12, ns.name("H")));
-        assert_eq!(toks.next().unwrap(), Token::Bar(1, 13, ns.name("|")));
-        assert_eq!(toks.next().unwrap(), Token::Var(1, 14, ns.name("T")));
-        assert_eq!(toks.next().unwrap(), Token::BracketClose(1, 15));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(1, 16));
-        assert_eq!(toks.next().unwrap(), Token::Dot(1, 17));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("member")));
+        assert_eq!(toks.next().unwrap(), Token::ParenOpen(1, 7, 6));
+        assert_eq!(toks.next().unwrap(), Token::Var(1, 8, 7, ns.name("H")));
+        assert_eq!(toks.next().unwrap(), Token::Comma(1, 9, 8, ns.name(",")));
+        assert_eq!(toks.next().unwrap(), Token::BracketOpen(1, 11, 10));
+        assert_eq!(toks.next().unwrap(), Token::Var(1, 12, 11, ns.name("H")));
+        assert_eq!(toks.next().unwrap(), Token::Bar(1, 13, 12, ns.name("|")));
+        assert_eq!(toks.next().unwrap(), Token::Var(1, 14, 13, ns.name("T")));
+        assert_eq!(toks.next().unwrap(), Token::BracketClose(1, 15, 14));
+        assert_eq!(toks.next().unwrap(), Token::ParenClose(1, 16, 15));
+        assert_eq!(toks.next().unwrap(), Token::Dot(1, 17, 16));
 
         // member(X, [_|T]) :- member(X, T).
-        assert_eq!(toks.next().unwrap(), Token::Funct(2, 1, ns.name("member")));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(2, 7));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 8, ns.name("X")));
-        assert_eq!(toks.next().unwrap(), Token::Comma(2, 9, ns.name(",")));
-        assert_eq!(toks.next().unwrap(), Token::BracketOpen(2, 11));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 12, ns.name("_")));
-        assert_eq!(toks.next().unwrap(), Token::Bar(2, 13, ns.name("|")));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 14, ns.name("T")));
-        assert_eq!(toks.next().unwrap(), Token::BracketClose(2, 15));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(2, 16));
-        assert_eq!(toks.next().unwrap(), Token::Funct(2, 18, ns.name(":-")));
-        assert_eq!(toks.next().unwrap(), Token::Funct(2, 21, ns.name("member")));
-        assert_eq!(toks.next().unwrap(), Token::ParenOpen(2, 27));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 28, ns.name("X")));
-        assert_eq!(toks.next().unwrap(), Token::Comma(2, 29, ns.name(",")));
-        assert_eq!(toks.next().unwrap(), Token::Var(2, 31, ns.name("T")));
-        assert_eq!(toks.next().unwrap(), Token::ParenClose(2, 32));
-        assert_eq!(toks.next().unwrap(), Token::Dot(2, 33));
+        assert_eq!(toks.next().unwrap(), Token::Funct(2, 1, 18, ns.name("member")));
+        assert_eq!(toks.next().unwrap(), Token::ParenOpen(2, 7, 24));
+        assert_eq!(toks.next().unwrap(), Token::Var(2, 8, 25, ns.name("X")));
+        assert_eq!(toks.next().unwrap(), Token::Comma(2, 9, 26, ns.name(",")));
+        assert_eq!(toks.next().unwrap(), Token::BracketOpen(2, 11, 28));
+        assert_eq!(toks.next().unwrap(), Token::Var(2, 12, 29, ns.name("_")));
+        assert_eq!(toks.next().unwrap(), Token::Bar(2, 13, 30, ns.name("|")));
+        assert_eq!(toks.next().unwrap(), Token::Var(2, 14, 31, ns.name("T")));
+        assert_eq!(toks.next().unwrap(), Token::BracketClose(2, 15, 32));
+        assert_eq!(toks.next().unwrap(), Token::ParenClose(2, 16, 33));
+        assert_eq!(toks.next().unwrap(), Token::Funct(2, 18, 35, ns.name(":-")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(2, 21, 38, ns.name("member")));
+        assert_eq!(toks.next().unwrap(), Token::ParenOpen(2, 27, 44));
+        assert_eq!(toks.next().unwrap(), Token::Var(2, 28, 45, ns.name("X")));
+        assert_eq!(toks.next().unwrap(), Token::Comma(2, 29, 46, ns.name(",")));
+        assert_eq!(toks.next().unwrap(), Token::Var(2, 31, 48, ns.name("T")));
+        assert_eq!(toks.next().unwrap(), Token::ParenClose(2, 32, 49));
+        assert_eq!(toks.next().unwrap(), Token::Dot(2, 33, 50));
+        assert_eq!(toks.next().unwrap(), Token::Eof(3, 1, 52));
+
+        assert!(toks.next().is_none());
+    }
 
+    #[test]
+    fn block_comments_are_skipped_by_default() {
+        let pl = "foo /* a comment */ bar.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 21, 20, ns.name("bar")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(1, 24, 23));
+        assert_eq!(toks.next().unwrap(), Token::Eof(2, 1, 25));
         assert!(toks.next().is_none());
     }
+
+    #[test]
+    fn block_comments_span_multiple_lines() {
+        let pl = "foo /* a\nmultiline\ncomment */ bar.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(3, 12, 30, ns.name("bar")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(3, 15, 33));
+        assert_eq!(toks.next().unwrap(), Token::Eof(4, 1, 35));
+        assert!(toks.next().is_none());
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        let pl = "foo /* outer /* inner */ still outer */ bar.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 41, 40, ns.name("bar")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(1, 44, 43));
+        assert_eq!(toks.next().unwrap(), Token::Eof(2, 1, 45));
+        assert!(toks.next().is_none());
+    }
+
+    #[test]
+    fn quoted_atoms_span_multiple_lines() {
+        let pl = "foo 'hello\nworld' bar.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 5, 4, ns.name("hello\nworld")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(2, 8, 18, ns.name("bar")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(2, 11, 21));
+        assert_eq!(toks.next().unwrap(), Token::Eof(3, 1, 23));
+        assert!(toks.next().is_none());
+    }
+
+    #[test]
+    fn char_code_literals_yield_codepoints() {
+        let pl = "0'a 0'\\n 0'\\\\.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Int(1, 1, 0, 'a' as i64));
+        assert_eq!(toks.next().unwrap(), Token::Int(1, 5, 4, '\n' as i64));
+        assert_eq!(toks.next().unwrap(), Token::Int(1, 10, 9, '\\' as i64));
+        assert_eq!(toks.next().unwrap(), Token::Dot(1, 14, 13));
+        assert_eq!(toks.next().unwrap(), Token::Eof(2, 1, 15));
+        assert!(toks.next().is_none());
+    }
+
+    #[test]
+    fn hex_float_literals_compute_mantissa_times_power_of_two() {
+        let pl = "0x1.8p3 0x.8p1 0x1p4.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Float(1, 1, 0, 12.0));
+        assert_eq!(toks.next().unwrap(), Token::Float(1, 9, 8, 1.0));
+        assert_eq!(toks.next().unwrap(), Token::Float(1, 16, 15, 16.0));
+        assert_eq!(toks.next().unwrap(), Token::Dot(1, 21, 20));
+        assert_eq!(toks.next().unwrap(), Token::Eof(2, 1, 22));
+        assert!(toks.next().is_none());
+    }
+
+    #[test]
+    fn hex_float_without_exponent_is_an_error() {
+        let pl = "0x1.8 foo.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        match toks.next().unwrap() {
+            Token::Err(err) => assert_eq!(err, SyntaxError::unexpected(1, 1, 0, "hex exponent")),
+            tok => panic!("expected an error, got {:?}", tok),
+        }
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 7, 6, ns.name("foo")));
+    }
+
+    #[test]
+    fn quoted_atoms_support_hex_and_octal_escapes() {
+        let pl = "'A\\x42\\C' 'A\\102\\C'.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("ABC")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 11, 10, ns.name("ABC")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(1, 20, 19));
+        assert_eq!(toks.next().unwrap(), Token::Eof(2, 1, 21));
+        assert!(toks.next().is_none());
+    }
+
+    #[test]
+    fn quoted_atoms_support_backslash_newline_continuation() {
+        let pl = "'ab\\\ncd' bar.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("abcd")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(2, 5, 9, ns.name("bar")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(2, 8, 12));
+        assert_eq!(toks.next().unwrap(), Token::Eof(3, 1, 14));
+        assert!(toks.next().is_none());
+    }
+
+    #[test]
+    fn unterminated_quote_across_lines_is_an_error() {
+        let pl = "foo 'hello\nworld\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        match toks.next().unwrap() {
+            Token::Err(err) => assert_eq!(err, SyntaxError::unbalanced(1, 5, 4, '\'')),
+            tok => panic!("expected an error, got {:?}", tok),
+        }
+    }
+
+    #[test]
+    fn confusing_unicode_is_an_error_when_disallowed() {
+        let pl = "foo\u{202e}bar.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns).allow_confusing_unicode(false);
+        match toks.next().unwrap() {
+            Token::Err(err) => assert_eq!(err, SyntaxError::confusing_unicode(1, 4, 3, '\u{202e}')),
+            tok => panic!("expected an error, got {:?}", tok),
+        }
+    }
+
+    #[test]
+    fn comments_report_their_text_when_requested() {
+        let pl = "foo. % a comment\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns).report_space(true);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(1, 4, 3));
+        assert_eq!(toks.next().unwrap(), Token::Space(1, 5, 4, ns.name(" ")));
+        assert_eq!(toks.next().unwrap(), Token::Comment(1, 6, 5, ns.name("% a comment")));
+        assert_eq!(toks.next().unwrap(), Token::Space(1, 17, 16, ns.name("\n")));
+        assert_eq!(toks.next().unwrap(), Token::Eof(2, 1, 17));
+    }
+
+    #[test]
+    fn block_comments_report_their_text_when_requested() {
+        let pl = "foo /* a\ncomment */ bar.\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns).report_space(true);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        assert_eq!(toks.next().unwrap(), Token::Space(1, 4, 3, ns.name(" ")));
+        assert_eq!(toks.next().unwrap(), Token::Comment(1, 5, 4, ns.name(" a\ncomment ")));
+        assert_eq!(toks.next().unwrap(), Token::Space(2, 11, 19, ns.name(" ")));
+        assert_eq!(toks.next().unwrap(), Token::Funct(2, 12, 20, ns.name("bar")));
+        assert_eq!(toks.next().unwrap(), Token::Dot(2, 15, 23));
+        assert_eq!(toks.next().unwrap(), Token::Space(2, 16, 24, ns.name("\n")));
+        assert_eq!(toks.next().unwrap(), Token::Eof(3, 1, 25));
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_an_error() {
+        let pl = "foo /* never closed\n";
+        let ns = NameSpace::new();
+        let mut toks = Lexer::new(pl.as_bytes(), &ns);
+        assert_eq!(toks.next().unwrap(), Token::Funct(1, 1, 0, ns.name("foo")));
+        match toks.next().unwrap() {
+            Token::Err(err) => assert_eq!(err, SyntaxError::unterminated_comment(1, 5, 4)),
+            tok => panic!("expected an error, got {:?}", tok),
+        }
+    }
+
+    #[test]
+    fn lex_drains_into_a_vec_ending_in_eof() {
+        let pl = "foo(X).\n";
+        let ns = NameSpace::new();
+        let toks = lex(pl.as_bytes(), &ns).unwrap();
+        assert_eq!(toks, vec![
+            Token::Funct(1, 1, 0, ns.name("foo")),
+            Token::ParenOpen(1, 4, 3),
+            Token::Var(1, 5, 4, ns.name("X")),
+            Token::ParenClose(1, 6, 5),
+            Token::Dot(1, 7, 6),
+            Token::Eof(2, 1, 8),
+        ]);
+    }
+
+    #[test]
+    fn lex_stops_at_the_first_error() {
+        let pl = "foo 'unterminated\n";
+        let ns = NameSpace::new();
+        match lex(pl.as_bytes(), &ns) {
+            Err(err) => assert_eq!(err, SyntaxError::unbalanced(1, 5, 4, '\'')),
+            Ok(toks) => panic!("expected an error, got {:?}", toks),
+        }
+    }
 }
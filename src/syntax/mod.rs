@@ -2,17 +2,22 @@ pub mod lexer;
 pub mod namespace;
 pub mod operators;
 pub mod parser;
+pub mod visit;
+pub mod writer;
 mod error;
 mod repr;
 
+#[cfg(test)]
+pub mod test_util;
+
 pub use self::error::{Result, SyntaxError};
-pub use self::repr::{Structure, Symbol};
+pub use self::repr::{Structure, Symbol, Position, Trivia, TriviaKind};
 use self::namespace::*;
 use self::operators::*;
 use self::parser::*;
 
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::Path;
 use std::mem;
 
@@ -54,7 +59,20 @@ impl<'a> Context<'a> {
     ///
     /// A `Parser` is an iterator over `Result<Box<Structure>, SyntaxError>`.
     pub fn parse<B: BufRead>(&self, reader: B) -> Parser<B> {
-        Parser::new(reader, &self.ns, &self.ops)
+        Parser::new(reader, &self.ns, self.ops.clone())
+    }
+
+    /// Parse some buffered reader, guaranteeing the iterator runs to the end
+    /// of the input rather than stopping at the first syntax error.
+    ///
+    /// This is the same as `parse`, since a `Parser` already resynchronizes
+    /// at the next clause terminator by default; it exists so that callers
+    /// who want every error in a file (rather than just the first) don't have
+    /// to know that `fail_fast(true)` is the one setting to avoid. Every
+    /// error encountered along the way, and the clause that raised it, is
+    /// still recorded and can be retrieved with `Parser::errs`.
+    pub fn parse_resilient<B: BufRead>(&self, reader: B) -> Parser<B> {
+        self.parse(reader).fail_fast(false)
     }
 
     /// Parse a file at the given path.
@@ -66,6 +84,15 @@ impl<'a> Context<'a> {
         let bf = BufReader::new(f);
         self.parse(bf)
     }
+
+    /// Writes a `Structure` as Prolog source text, using this context's
+    /// operator table to decide which functors print as operators.
+    ///
+    /// Variables are named `_G<n>`; use `writer::write` directly to supply
+    /// real names instead.
+    pub fn write<W: Write>(&self, st: &Structure, out: &mut W) -> io::Result<()> {
+        writer::write(st, &self.ops, &[], out)
+    }
 }
 
 #[cfg(test)]
@@ -102,4 +129,19 @@ mod test {
         assert_eq!(parser.next().unwrap().unwrap().as_slice(), second);
         assert_eq!(parser.next(), None);
     }
+
+    #[test]
+    fn parse_resilient_recovers_the_clauses_around_a_broken_one() {
+        let ctx = Context::new();
+
+        let pl = "foo.\n\
+                  bar(X)) .\n\
+                  baz.\n";
+
+        let mut parser = ctx.parse_resilient(pl.as_bytes());
+        assert_eq!(parser.next().unwrap().as_slice(), &[Funct(0, ctx.ns.name("foo"))]);
+        assert_eq!(parser.next().unwrap().as_slice(), &[Funct(0, ctx.ns.name("baz"))]);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.errs().count(), 1);
+    }
 }
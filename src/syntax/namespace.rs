@@ -17,23 +17,116 @@
 //! [`NameSpace`]: ./struct.NameSpace.html
 //! [`Name`]: ./struct.Name.html
 
-use std::cell::RefCell;
 use std::cmp::{Ordering, PartialOrd};
 use std::collections::HashSet;
 use std::fmt;
-use std::marker::PhantomData;
-use std::mem;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::ptr;
+use std::sync::Mutex;
 
 /// Assigns `Name`s to strings.
 ///
 /// Equivalent strings will be assigned the same `Name`.
 ///
-/// A `NameSpace` is effectivly a string interner.
+/// A `NameSpace` is effectivly a string interner. Strings are copied into an
+/// internal [`Arena`] that never moves or frees them once allocated, so a
+/// `Name`'s `&'ns str` is a perfectly ordinary borrow of `self` rather than a
+/// reference whose lifetime was laundered through `mem::transmute`. The
+/// dedup index and the arena are both guarded by a `Mutex` rather than a
+/// `RefCell`, so unlike a naive interner, a `NameSpace` may be shared and
+/// interned into concurrently from multiple threads - useful when several
+/// parsers share one `Context`.
 pub struct NameSpace {
-    strings: RefCell<HashSet<Box<str>>>,
+    arena: Arena,
+    index: Mutex<HashSet<InternedStr>>,
 }
 
+/// A bump allocator that hands out strings whose addresses are stable for
+/// the arena's own lifetime.
+///
+/// Each interned string is copied into its own boxed buffer, and buffers are
+/// only ever appended to the chunk list, never reallocated or freed before
+/// the arena itself is dropped. A `Box`'s heap allocation does not move when
+/// the `Box` handle holding it is moved (e.g. by the `Vec` growing), so a
+/// pointer into a chunk stays valid for as long as the arena lives.
+struct Arena {
+    chunks: Mutex<Vec<Box<str>>>,
+}
+
+impl Arena {
+    fn new() -> Arena {
+        Arena { chunks: Mutex::new(Vec::new()) }
+    }
+
+    /// Copies `s` into arena-owned storage and returns a reference to the
+    /// copy, valid for as long as `'ns` - the lifetime of the shared borrow
+    /// of the `NameSpace` that owns this arena.
+    ///
+    /// SAFETY: this extends the lifetime of a fresh heap allocation to
+    /// `'ns`. That's sound here because the allocation is never moved or
+    /// freed except by `Arena`'s own destructor, and the arena outlives
+    /// `'ns` precisely because `'ns` is a borrow of the `NameSpace` that
+    /// owns it.
+    fn alloc<'ns>(&'ns self, s: &str) -> &'ns str {
+        let boxed: Box<str> = s.into();
+        let ptr: *const str = boxed.as_ref();
+        self.chunks.lock().unwrap().push(boxed);
+        unsafe { &*ptr }
+    }
+}
+
+/// A dedup-index entry: compares and hashes by the *contents* of the pointee
+/// rather than the pointer itself, so the index can look up whether a string
+/// has already been interned before an `Arena::alloc` call produces its
+/// stable address.
+///
+/// This is purely an implementation detail of `NameSpace::name`; the `Name`s
+/// handed out to callers compare by pointer, as documented on `Name` itself.
+#[derive(Clone, Copy)]
+struct InternedStr(*const str);
+
+impl InternedStr {
+    /// SAFETY: callers must only dereference this while the `NameSpace` that
+    /// produced it (and therefore its `Arena`) is still alive, which holds
+    /// for every use below: the index never outlives its `NameSpace`.
+    fn as_str(&self) -> &str {
+        unsafe { &*self.0 }
+    }
+
+    /// Reinterprets the pointee's lifetime as `'ns`, the lifetime of the
+    /// `NameSpace` that produced this entry.
+    ///
+    /// SAFETY: sound for the same reason as `Arena::alloc` - the pointee is
+    /// arena-owned and is never moved or freed before the `NameSpace` (and
+    /// its `Arena`) are dropped, and `'ns` never outlives the `NameSpace`.
+    fn extend<'ns>(&self) -> &'ns str {
+        unsafe { &*self.0 }
+    }
+}
+
+impl PartialEq for InternedStr {
+    fn eq(&self, other: &InternedStr) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for InternedStr {}
+
+impl Hash for InternedStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+// SAFETY: a `NameSpace`'s interior mutability is entirely behind `Mutex`es,
+// and the raw pointers in its dedup index are only ever dereferenced while
+// the `NameSpace` is alive (see `InternedStr::as_str`), so sharing or
+// sending a `NameSpace` across threads cannot produce a data race or a
+// dangling reference.
+unsafe impl Send for NameSpace {}
+unsafe impl Sync for NameSpace {}
+
 /// A lightweight representation of a string.
 ///
 /// A `Name` is almost exactly like a `&'ns str` where `'ns` is the lifetime
@@ -42,10 +135,8 @@ pub struct NameSpace {
 /// contents of the string. Thus `Name`s for the same string but from different
 /// `NameSpace`s are not equal.
 #[derive(Clone, Copy)]
-#[derive(PartialEq, Eq)]
 pub struct Name<'ns> {
-    ptr: *const str,
-    pha: PhantomData<&'ns str>,
+    s: &'ns str,
 }
 
 // NameSpace
@@ -54,7 +145,10 @@ pub struct Name<'ns> {
 impl NameSpace {
     /// Constructs a new `NameSpace`.
     pub fn new() -> NameSpace {
-        NameSpace { strings: RefCell::new(HashSet::new()) }
+        NameSpace {
+            arena: Arena::new(),
+            index: Mutex::new(HashSet::new()),
+        }
     }
 
     /// Returns a `Name` for the token.
@@ -62,27 +156,23 @@ impl NameSpace {
     where
         S: Into<String> + AsRef<str>,
     {
-        // If the token is already in the set,
-        // fetch the old key and convert it into a Name
-        {
-            let strings = self.strings.borrow();
-            if let Some(s) = strings.get(tok.as_ref()) {
-                let s = unsafe { mem::transmute::<&str, &'ns str>(s) };
-                return Name::from(s);
-            }
+        // If the token is already in the set, reuse its existing address.
+        // The index stays locked until a new address is recorded, so two
+        // threads interning the same string cannot both allocate it.
+        let mut index = self.index.lock().unwrap();
+        if let Some(interned) = index.get(&InternedStr(tok.as_ref() as *const str)) {
+            return Name { s: interned.extend() };
         }
 
-        // Otherwise, turn this token into a name and insert it into the set.
-        let mut strings = self.strings.borrow_mut();
-        let boxed = tok.into().into_boxed_str();
-        let s = unsafe { mem::transmute::<&str, &'ns str>(boxed.as_ref()) };
-        strings.insert(boxed);
-        Name::from(s)
+        // Otherwise, copy it into the arena and record its new address.
+        let s = self.arena.alloc(tok.as_ref());
+        index.insert(InternedStr(s as *const str));
+        Name { s: s }
     }
 
     /// Returns the number of unique `Name`s issued.
     pub fn len(&self) -> usize {
-        self.strings.borrow().len()
+        self.index.lock().unwrap().len()
     }
 }
 
@@ -91,16 +181,13 @@ impl NameSpace {
 
 impl<'ns> Name<'ns> {
     pub fn as_str(&self) -> &'ns str {
-        unsafe { mem::transmute(self.ptr) }
+        self.s
     }
 }
 
 impl<'ns> From<&'ns str> for Name<'ns> {
     fn from(string: &'ns str) -> Name {
-        Name {
-            ptr: string as *const str,
-            pha: PhantomData,
-        }
+        Name { s: string }
     }
 }
 
@@ -123,6 +210,21 @@ impl<'ns> Deref for Name<'ns> {
     }
 }
 
+impl<'ns> PartialEq for Name<'ns> {
+    fn eq(&self, other: &Name<'ns>) -> bool {
+        ptr::eq(self.s, other.s)
+    }
+}
+
+impl<'ns> Eq for Name<'ns> {}
+
+impl<'ns> Hash for Name<'ns> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.s.as_ptr() as usize).hash(state);
+        self.s.len().hash(state);
+    }
+}
+
 impl<'ns> PartialOrd for Name<'ns> {
     fn partial_cmp(&self, other: &Name<'ns>) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -143,7 +245,7 @@ impl<'ns> fmt::Display for Name<'ns> {
 
 impl<'ns> fmt::Debug for Name<'ns> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}@{:?}", self.as_str(), self.ptr)
+        write!(f, "{:?}@{:?}", self.as_str(), self.s.as_ptr())
     }
 }
 
@@ -194,4 +296,10 @@ mod test {
         assert_eq!(a, b);
         assert_ne!(b, c);
     }
+
+    #[test]
+    fn name_space_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<NameSpace>();
+    }
 }
@@ -39,6 +39,7 @@ pub enum Op<'ns> {
 /// -`FX` and `FY` operators are `Prefix`.
 /// -`XFX`, `XFY`, and `YFX`, operators are `Infix`.
 /// -`XF` and `YF` operators are `Postfix`.
+#[derive(Debug)]
 #[derive(Clone, Copy)]
 #[derive(PartialEq, Eq)]
 #[derive(PartialOrd, Ord)]
@@ -53,7 +54,39 @@ pub enum OpType {
 /// The table is implemented as a sorted list of `Op`s. Operators are sorted
 /// first by name, then by type, and finally by precedence.
 #[derive(Debug)]
-pub struct OpTable<'ns>(Vec<Op<'ns>>);
+#[derive(Clone)]
+pub struct OpTable<'ns> {
+    ops: Vec<Op<'ns>>,
+
+    /// A log of mutations applied to `ops`, used to support cheap
+    /// [`snapshot`](OpTable::snapshot)/[`restore`](OpTable::restore) without
+    /// cloning the whole table.
+    journal: Vec<Change<'ns>>,
+}
+
+/// A single mutation recorded in an `OpTable`'s journal, along with enough
+/// information to undo it.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+enum Change<'ns> {
+    /// `op` was freshly added; undo by removing it.
+    Added(Op<'ns>),
+    /// `op` occupied the slot before being overwritten; undo by restoring it.
+    Replaced(Op<'ns>),
+    /// `op` was removed; undo by re-inserting it.
+    Removed(Op<'ns>),
+}
+
+/// An opaque marker captured by [`OpTable::snapshot`] and consumed by
+/// [`OpTable::restore`] to undo every mutation made since the snapshot was
+/// taken.
+///
+/// Snapshots only make sense for the `OpTable` that produced them; restoring
+/// one against a different table (or one that has already been restored past
+/// the snapshot point) is a logic error.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub struct OpTableSnapshot(usize);
 
 // OpTable
 // --------------------------------------------------
@@ -61,12 +94,12 @@ pub struct OpTable<'ns>(Vec<Op<'ns>>);
 impl<'ns> OpTable<'ns> {
     /// Construct a new, empty operator table.
     pub fn new() -> OpTable<'ns> {
-        OpTable(Vec::new())
+        OpTable { ops: Vec::new(), journal: Vec::new() }
     }
 
     /// View the table as a sorted slice of `Op`s.
     pub fn as_slice(&self) -> &[Op<'ns>] {
-        &self.0
+        &self.ops
     }
 
     /// Insert a new operator into the table.
@@ -74,8 +107,48 @@ impl<'ns> OpTable<'ns> {
     /// TODO: remove any conflicting operators.
     pub fn insert(&mut self, op: Op<'ns>) {
         match self.binary_search(&op) {
-            Ok(i) => self.0[i] = op,
-            Err(i) => self.0.insert(i, op),
+            Ok(i) => {
+                self.journal.push(Change::Replaced(self.ops[i]));
+                self.ops[i] = op;
+            }
+            Err(i) => {
+                self.journal.push(Change::Added(op));
+                self.ops.insert(i, op);
+            }
+        }
+    }
+
+    /// Captures the current state of the table so it can later be restored
+    /// with [`restore`](OpTable::restore).
+    ///
+    /// This is cheap: rather than cloning the table, it just notes how many
+    /// mutations have been journaled so far. Nesting snapshots (e.g. for
+    /// included files) works as long as they are restored in LIFO order.
+    pub fn snapshot(&self) -> OpTableSnapshot {
+        OpTableSnapshot(self.journal.len())
+    }
+
+    /// Undoes every mutation made since `snap` was captured, restoring the
+    /// table to its state at that point.
+    pub fn restore(&mut self, snap: OpTableSnapshot) {
+        while self.journal.len() > snap.0 {
+            match self.journal.pop().unwrap() {
+                Change::Added(op) => {
+                    if let Ok(i) = self.ops.binary_search(&op) {
+                        self.ops.remove(i);
+                    }
+                }
+                Change::Replaced(prev) => {
+                    if let Ok(i) = self.ops.binary_search(&prev) {
+                        self.ops[i] = prev;
+                    }
+                }
+                Change::Removed(prev) => {
+                    if let Err(i) = self.ops.binary_search(&prev) {
+                        self.ops.insert(i, prev);
+                    }
+                }
+            }
         }
     }
 
@@ -142,6 +215,288 @@ impl<'ns> OpTable<'ns> {
     }
 }
 
+// Runtime `op/3` management
+// --------------------------------------------------
+
+/// The fixity and associativity of an operator, independent of its priority
+/// or name.
+///
+/// This mirrors the discriminants of [`Op`] but without the payload, so it
+/// can be passed around as the `Type` argument of an `op/3` directive before
+/// the final `Op` is assembled.
+///
+/// [`Op`]: ./enum.Op.html
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq)]
+pub enum Fixity {
+    XF,
+    YF,
+    XFX,
+    XFY,
+    YFX,
+    FY,
+    FX,
+}
+
+/// An error raised while defining or removing an operator via [`OpTable::define`].
+///
+/// [`OpTable::define`]: ./struct.OpTable.html#method.define
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq)]
+pub enum OpError {
+    /// The priority was not `0` (delete) nor in the ISO range `1..=1200`.
+    PriorityOutOfRange(u32),
+
+    /// The name is already defined with the opposite of infix/postfix, which
+    /// ISO forbids a single name from holding simultaneously.
+    InfixPostfixClash,
+
+    /// The name is not allowed to be redefined as an operator (e.g. `,`).
+    ProtectedOperator,
+}
+
+impl Fixity {
+    #[inline]
+    fn op_type(&self) -> OpType {
+        match *self {
+            Fixity::FX | Fixity::FY => OpType::Prefix,
+            Fixity::XFX | Fixity::XFY | Fixity::YFX => OpType::Infix,
+            Fixity::XF | Fixity::YF => OpType::Postfix,
+        }
+    }
+
+    #[inline]
+    fn with<'ns>(&self, prec: u32, name: Name<'ns>) -> Op<'ns> {
+        match *self {
+            Fixity::XF => Op::XF(prec, name),
+            Fixity::YF => Op::YF(prec, name),
+            Fixity::XFX => Op::XFX(prec, name),
+            Fixity::XFY => Op::XFY(prec, name),
+            Fixity::YFX => Op::YFX(prec, name),
+            Fixity::FY => Op::FY(prec, name),
+            Fixity::FX => Op::FX(prec, name),
+        }
+    }
+}
+
+impl<'ns> OpTable<'ns> {
+    /// Defines or removes an operator, following the semantics of ISO
+    /// `op/3`.
+    ///
+    /// A `priority` of `0` removes the operator matching `fixity`'s type
+    /// class (prefix, infix, or postfix) and `name`, if any. Otherwise the
+    /// priority must lie in `1..=1200`. The name `,` may never be redefined,
+    /// and a name may not be simultaneously infix and postfix: defining one
+    /// while the other is present is rejected rather than silently replacing
+    /// it.
+    pub fn define(&mut self, priority: u32, fixity: Fixity, name: Name<'ns>) -> Result<(), OpError> {
+        if name.as_str() == "," {
+            return Err(OpError::ProtectedOperator);
+        }
+
+        if priority == 0 {
+            self.remove_type(fixity.op_type(), name);
+            return Ok(());
+        }
+
+        if priority > 1200 {
+            return Err(OpError::PriorityOutOfRange(priority));
+        }
+
+        let other = match fixity.op_type() {
+            OpType::Infix => OpType::Postfix,
+            OpType::Postfix => OpType::Infix,
+            OpType::Prefix => OpType::Prefix, // prefix never clashes
+        };
+        if other != OpType::Prefix && self.has_type(other, name) {
+            return Err(OpError::InfixPostfixClash);
+        }
+
+        self.insert(fixity.with(priority, name));
+        Ok(())
+    }
+
+    /// Returns true if any operator of the given type class and name exists.
+    fn has_type(&self, op_type: OpType, name: Name<'ns>) -> bool {
+        self.get(name).iter().any(|op| op.op_type() == op_type)
+    }
+
+    /// Removes every operator matching the given type class and name.
+    fn remove_type(&mut self, op_type: OpType, name: Name<'ns>) {
+        let journal = &mut self.journal;
+        self.ops.retain(|op| {
+            let matches = op.op_type() == op_type && op.name() == name;
+            if matches {
+                journal.push(Change::Removed(*op));
+            }
+            !matches
+        });
+    }
+}
+
+// Introspection
+// --------------------------------------------------
+
+impl<'ns> OpTable<'ns> {
+    /// Returns `(priority, type, name)` triples for every operator in the
+    /// table, suitable for backing a `current_op/3` builtin.
+    pub fn current_ops(&self) -> Vec<(u32, OpType, Name<'ns>)> {
+        self.ops.iter().map(|op| (op.prec(), op.op_type(), op.name())).collect()
+    }
+
+    /// Reports pairs of definitions that cannot legally coexist: the same
+    /// name defined as both infix and postfix, or more than one definition
+    /// within the same type class at mismatched priorities.
+    pub fn conflicts(&self) -> Vec<(Op<'ns>, Op<'ns>)> {
+        let mut conflicts = Vec::new();
+        let mut seen: Vec<Name<'ns>> = Vec::new();
+
+        for op in self.ops.iter() {
+            if seen.contains(&op.name()) {
+                continue;
+            }
+            seen.push(op.name());
+
+            let group = self.get(op.name());
+            for i in 0..group.len() {
+                for j in (i + 1)..group.len() {
+                    let (a, b) = (group[i], group[j]);
+                    let infix_postfix_clash = (a.op_type() == OpType::Infix &&
+                                                b.op_type() == OpType::Postfix) ||
+                        (a.op_type() == OpType::Postfix && b.op_type() == OpType::Infix);
+                    let mismatched_duplicate = a.op_type() == b.op_type() && a.prec() != b.prec();
+                    if infix_postfix_clash || mismatched_duplicate {
+                        conflicts.push((a, b));
+                    }
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+// Operator-Precedence Reading
+// --------------------------------------------------
+
+/// A minimal view of a token stream sufficient to drive [`OpTable::read`].
+///
+/// This keeps the precedence-climbing reader independent of any particular
+/// lexer or token representation: implementors only need to expose enough
+/// information about the upcoming token(s) to decide whether a name should
+/// be read as a prefix operator, an infix/postfix operator, a compound-term
+/// functor, or a plain atom.
+///
+/// [`OpTable::read`]: ./struct.OpTable.html#method.read
+pub trait TermTokens<'ns> {
+    /// Returns the name of the upcoming token if it names an atom or
+    /// operator, without consuming it.
+    fn peek_name(&mut self) -> Option<Name<'ns>>;
+
+    /// True if the token directly following the peeked name is `(` with no
+    /// space in between, which forces the name to be read as a compound-term
+    /// functor rather than an operator or atom.
+    fn peek_is_compound(&mut self) -> bool;
+
+    /// True if no further token could begin a term, so a trailing operator
+    /// name must be read as a plain atom instead of being applied.
+    fn at_term_end(&mut self) -> bool;
+
+    /// Consumes and returns the name peeked by `peek_name`.
+    fn bump_name(&mut self) -> Name<'ns>;
+}
+
+impl<'ns> OpTable<'ns> {
+    /// Reads a single term from `tokens`, returning the priority of the term
+    /// that was read, or `None` if `tokens` had nothing left to read.
+    ///
+    /// This implements precedence climbing: a primary is read first — a bare
+    /// atom, a compound term (name immediately followed by `(`), or a prefix
+    /// operator whose priority is `<= max_prec`, in which case its argument
+    /// is read recursively at `p - 1` for `FX` or `p` for `FY`. A prefix
+    /// operator name that cannot begin a further term (`at_term_end`) is read
+    /// as a plain atom instead, which also covers a bare operator atom used
+    /// as an argument (e.g. `-` alone).
+    ///
+    /// The reader then loops, consuming infix/postfix operators compatible
+    /// with the priority of the term read so far via [`get_compatible`],
+    /// recursing on the right-hand argument of infix operators at `p - 1`
+    /// for `XFX`/`YFX` or `p` for `XFY`.
+    ///
+    /// [`get_compatible`]: #method.get_compatible
+    pub fn read<T: TermTokens<'ns>>(&self, tokens: &mut T, max_prec: u32) -> Option<u32> {
+        let mut prec = self.read_primary(tokens, max_prec)?;
+        loop {
+            match tokens.peek_name() {
+                Some(name) => {
+                    match self.get_compatible(name, prec, max_prec) {
+                        Some(op) => {
+                            tokens.bump_name();
+                            match op {
+                                Op::XFY(p, _) => {
+                                    self.read(tokens, p);
+                                }
+                                Op::YFX(p, _) | Op::XFX(p, _) => {
+                                    self.read(tokens, p - 1);
+                                }
+                                _ => (), // postfix: the argument is already read.
+                            }
+                            // The resulting term's own priority is the operator's, regardless
+                            // of how tightly its operands happened to bind, as in `Parser::read`.
+                            prec = op.prec();
+                        }
+                        None => break,
+                    }
+                }
+                None => break,
+            }
+        }
+        Some(prec)
+    }
+
+    /// Reads a primary term: an atom, a compound term, or a prefix operator
+    /// application. See [`read`] for the full algorithm.
+    ///
+    /// [`read`]: #method.read
+    fn read_primary<T: TermTokens<'ns>>(&self, tokens: &mut T, max_prec: u32) -> Option<u32> {
+        let name = tokens.peek_name()?;
+
+        // A name immediately followed by `(` is always a compound term,
+        // never an operator.
+        if tokens.peek_is_compound() {
+            tokens.bump_name();
+            return Some(0);
+        }
+
+        // A name with nothing left to read after it cannot be a prefix
+        // operator application, so it must be a plain atom. This is also how
+        // a bare operator atom (e.g. `-` used as an argument) is read.
+        if tokens.at_term_end() {
+            tokens.bump_name();
+            return Some(0);
+        }
+
+        match self.get_prefix(name, max_prec) {
+            Some(Op::FX(p, _)) => {
+                tokens.bump_name();
+                self.read(tokens, p - 1);
+                Some(p)
+            }
+            Some(Op::FY(p, _)) => {
+                tokens.bump_name();
+                self.read(tokens, p);
+                Some(p)
+            }
+            _ => {
+                tokens.bump_name();
+                Some(0)
+            }
+        }
+    }
+}
+
 impl<'ns> From<Vec<Op<'ns>>> for OpTable<'ns> {
     fn from(mut vec: Vec<Op<'ns>>) -> OpTable<'ns> {
         vec.sort();
@@ -153,7 +508,7 @@ impl<'ns> From<Vec<Op<'ns>>> for OpTable<'ns> {
                 i += 1;
             }
         }
-        OpTable(vec)
+        OpTable { ops: vec, journal: Vec::new() }
     }
 }
 
@@ -364,4 +719,192 @@ mod test {
             Op::FX(3, zap),
         ]);
     }
+
+    #[test]
+    fn define_and_remove() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let mut ops = OpTable::new();
+
+        ops.define(500, Fixity::YFX, foo).unwrap();
+        assert_eq!(ops.get_infix(foo, 500), Some(Op::YFX(500, foo)));
+
+        ops.define(0, Fixity::YFX, foo).unwrap();
+        assert_eq!(ops.get_infix(foo, 500), None);
+    }
+
+    #[test]
+    fn define_rejects_bad_priority() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let mut ops = OpTable::new();
+        assert_eq!(ops.define(1201, Fixity::XFX, foo), Err(OpError::PriorityOutOfRange(1201)));
+    }
+
+    #[test]
+    fn define_rejects_comma() {
+        let ns = NameSpace::new();
+        let comma = ns.name(",");
+        let mut ops = OpTable::new();
+        assert_eq!(ops.define(999, Fixity::XFY, comma), Err(OpError::ProtectedOperator));
+    }
+
+    #[test]
+    fn define_rejects_infix_postfix_clash() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let mut ops = OpTable::new();
+        ops.define(500, Fixity::XF, foo).unwrap();
+        assert_eq!(ops.define(500, Fixity::YFX, foo), Err(OpError::InfixPostfixClash));
+    }
+
+    /// A trivial token stream over pre-named atoms, used to exercise
+    /// `OpTable::read` without a real lexer.
+    struct NameTokens<'ns> {
+        names: Vec<Name<'ns>>,
+        pos: usize,
+    }
+
+    impl<'ns> TermTokens<'ns> for NameTokens<'ns> {
+        fn peek_name(&mut self) -> Option<Name<'ns>> {
+            self.names.get(self.pos).cloned()
+        }
+
+        fn peek_is_compound(&mut self) -> bool {
+            false
+        }
+
+        fn at_term_end(&mut self) -> bool {
+            self.pos + 1 >= self.names.len()
+        }
+
+        fn bump_name(&mut self) -> Name<'ns> {
+            let name = self.names[self.pos];
+            self.pos += 1;
+            name
+        }
+    }
+
+    #[test]
+    fn read_infix_chain() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let mut tokens = NameTokens {
+            names: vec![ns.name("a"), ns.name("*"), ns.name("b"), ns.name("+"), ns.name("c")],
+            pos: 0,
+        };
+        assert_eq!(ops.read(&mut tokens, 1200), Some(500));
+        assert_eq!(tokens.pos, tokens.names.len());
+    }
+
+    #[test]
+    fn xfx_chain_is_rejected() {
+        let ns = NameSpace::new();
+        let is_eq = ns.name("is_eq");
+        let mut ops = OpTable::new();
+        ops.define(700, Fixity::XFX, is_eq).unwrap();
+
+        // `a is_eq b is_eq c`: xfx cannot chain at equal priority, so the
+        // second `is_eq` must be left unconsumed rather than applied.
+        let mut tokens = NameTokens {
+            names: vec![ns.name("a"), is_eq, ns.name("b"), is_eq, ns.name("c")],
+            pos: 0,
+        };
+        assert_eq!(ops.read(&mut tokens, 1200), Some(700));
+        assert_eq!(tokens.pos, 3);
+    }
+
+    #[test]
+    fn read_bare_prefix_atom() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let mut tokens = NameTokens { names: vec![ns.name("-")], pos: 0 };
+        assert_eq!(ops.read(&mut tokens, 1200), Some(0));
+        assert_eq!(tokens.pos, 1);
+    }
+
+    #[test]
+    fn current_ops() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let ops = OpTable::from(&[Op::FX(500, foo)][..]);
+        assert_eq!(ops.current_ops(), vec![(500, OpType::Prefix, foo)]);
+    }
+
+    #[test]
+    fn conflicts_reports_infix_postfix_clash() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let mut ops = OpTable::new();
+        ops.insert(Op::XF(500, foo));
+        ops.insert(Op::YFX(600, foo));
+        assert_eq!(ops.conflicts(), vec![(Op::YFX(600, foo), Op::XF(500, foo))]);
+    }
+
+    #[test]
+    fn conflicts_reports_mismatched_duplicates() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let mut ops = OpTable::new();
+        ops.insert(Op::FX(500, foo));
+        ops.insert(Op::FY(300, foo));
+        assert_eq!(ops.conflicts(), vec![(Op::FY(300, foo), Op::FX(500, foo))]);
+    }
+
+    #[test]
+    fn conflicts_empty_for_clean_table() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        assert_eq!(ops.conflicts(), Vec::new());
+    }
+
+    #[test]
+    fn snapshot_restore_undoes_inserts() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let mut ops = OpTable::default(&ns);
+        let before = ops.current_ops();
+
+        let snap = ops.snapshot();
+        ops.define(500, Fixity::XFX, foo).unwrap();
+        assert!(!ops.get(foo).is_empty());
+
+        ops.restore(snap);
+        assert_eq!(ops.current_ops(), before);
+    }
+
+    #[test]
+    fn snapshot_restore_undoes_removals() {
+        let ns = NameSpace::new();
+        let plus = ns.name("+");
+        let mut ops = OpTable::default(&ns);
+        let before = ops.current_ops();
+
+        let snap = ops.snapshot();
+        ops.define(0, Fixity::YFX, plus).unwrap();
+        assert!(ops.get_infix(plus, 1200).is_none());
+
+        ops.restore(snap);
+        assert_eq!(ops.current_ops(), before);
+    }
+
+    #[test]
+    fn nested_snapshots_restore_in_order() {
+        let ns = NameSpace::new();
+        let foo = ns.name("foo");
+        let bar = ns.name("bar");
+        let mut ops = OpTable::new();
+
+        let outer = ops.snapshot();
+        ops.define(500, Fixity::XFX, foo).unwrap();
+        let inner = ops.snapshot();
+        ops.define(300, Fixity::FY, bar).unwrap();
+
+        ops.restore(inner);
+        assert!(ops.get(bar).is_empty());
+        assert!(!ops.get(foo).is_empty());
+
+        ops.restore(outer);
+        assert!(ops.get(foo).is_empty());
+    }
 }
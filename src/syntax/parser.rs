@@ -3,8 +3,9 @@
 //! A parser lifts a buffered reader into an interator over term [`Structure`]s
 //! by way of a [`NameSpace`] and [`OpTable`]. The `NameSpace` will be used to
 //! assign names to the symbols of the `Structure`s, and the `OpTable` will be
-//! used to parse operators. The references to the `NameSpace` and `OpTable`
-//! are treated with a single lifetime, `'ctx`, because they are assumed to be
+//! used to parse operators and may itself be grown as `op/3` directives are
+//! parsed. The `NameSpace` reference and the names held by the `OpTable` are
+//! treated with a single lifetime, `'ctx`, because they are assumed to be
 //! owned by roughly the same calling context.
 //!
 //! Errors at both the I/O and syntax levels are saved into a buffer and may be
@@ -12,6 +13,18 @@
 //! structures emitted by the parser cannot be assumed to accurately represent
 //! the (possibly invalid) source program.
 //!
+//! A parser normally discards token positions once a `Structure` has been
+//! built, since the fast path only needs the finished tree. Opting in with
+//! [`spans`] makes it additionally record the [`Position`] of each `Symbol`,
+//! retrievable through [`last_spans`] once the structure is built, for tools
+//! that need to point a diagnostic at a specific subterm.
+//!
+//! Likewise, a parser normally never sees whitespace or comments at all, since
+//! the lexer filters them out before they reach it. Opting in with
+//! [`full_fidelity`] has the lexer report them and the parser record them as
+//! [`Trivia`], retrievable through [`last_trivia`], for pretty-printers and
+//! linters that need to round-trip the source text byte-for-byte.
+//!
 //! For more information on the syntax of logic programs, see the Wikipedia
 //! article on the [syntax and semantics of Prolog][1].
 //!
@@ -19,6 +32,12 @@
 //! [`NameSpace`]: ../namespace/struct.NameSpace.html
 //! [`OpTable`]: ../operators/struct.OpTable.html
 //! [`errs`]: #method.errs
+//! [`spans`]: struct.Parser.html#method.spans
+//! [`last_spans`]: struct.Parser.html#method.last_spans
+//! [`Position`]: ../repr/struct.Position.html
+//! [`full_fidelity`]: struct.Parser.html#method.full_fidelity
+//! [`last_trivia`]: struct.Parser.html#method.last_trivia
+//! [`Trivia`]: ../repr/struct.Trivia.html
 //!
 //! [1]: https://en.wikipedia.org/wiki/Prolog_syntax_and_semantics
 
@@ -29,31 +48,42 @@ use std::vec::Drain;
 use syntax::error::{SyntaxError, Result};
 use syntax::lexer::{Lexer, Token};
 use syntax::namespace::{NameSpace, Name};
-use syntax::operators::{OpTable, Op};
-use syntax::repr::{Structure, Symbol};
+use syntax::operators::{OpTable, Op, Fixity};
+use syntax::repr::{Structure, Symbol, Position, Trivia, TriviaKind};
 
 /// An iterator over [`Structure`]s in UTF-8 text.
 ///
 /// The parser requires a reference to a [`NameSpace`] to assign names to
-/// constants and a reference to an [`OpTable`] to specify the operators and
-/// their precedence. The lifetime `'ctx` refers to both references.
+/// constants, and it owns an [`OpTable`] to specify the operators and their
+/// precedence. The lifetime `'ctx` covers both the namespace reference and the
+/// names held by the operator table.
 ///
 /// The parser is implemented using the [precedence climbing method][1] and is
-/// independent of the set of operators. Further, the operator table is allowed
-/// to be modified at runtime.
+/// independent of the set of operators. Further, the operator table is owned
+/// rather than borrowed so that it can be mutated at runtime: a clause of the
+/// form `:- op(Priority, Type, Name)` registers the operator before the next
+/// clause is read, so a source file may define operators for its own later
+/// use. See [`as_op_directive`] for the exact directive shape recognized.
 ///
 /// [`Structure`]: ../repr/struct.Structure.html
 /// [`NameSpace`]: ../namespace/struct.NameSpace.html
 /// [`OpTable`]: ../operators/struct.OpTable.html
+/// [`as_op_directive`]: fn.as_op_directive.html
 ///
 /// [1]: https://en.wikipedia.org/wiki/Operator-precedence_parser#Precedence_climbing_method
 pub struct Parser<'ctx, B: BufRead> {
-    ops: &'ctx OpTable<'ctx>,
+    ops: OpTable<'ctx>,
     lexer: Lexer<'ctx, B>,
     peeked: Option<Token<'ctx>>,
     errs: Vec<SyntaxError>,
     vars: Vec<Name<'ctx>>,
     buf: Vec<Symbol<'ctx>>,
+    spans: Vec<Position>,
+    record_spans: bool,
+    trivia: Vec<Trivia<'ctx>>,
+    full_fidelity: bool,
+    fail_fast: bool,
+    done: bool,
 }
 
 // Public API
@@ -61,7 +91,11 @@ pub struct Parser<'ctx, B: BufRead> {
 
 impl<'ctx, B: BufRead> Parser<'ctx, B> {
     /// Constructs a new `Parser` from the given reader, namespace, and operator table.
-    pub fn new(reader: B, ns: &'ctx NameSpace, ops: &'ctx OpTable<'ctx>) -> Parser<'ctx, B> {
+    ///
+    /// The table is taken by value (rather than by reference) because the
+    /// parser may grow it in place as it processes `op/3` directives; clone
+    /// an existing table if its caller needs to keep its own copy unaffected.
+    pub fn new(reader: B, ns: &'ctx NameSpace, ops: OpTable<'ctx>) -> Parser<'ctx, B> {
         Parser {
             ops: ops,
             lexer: Lexer::new(reader, ns),
@@ -69,9 +103,76 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
             errs: Vec::new(),
             vars: Vec::with_capacity(32),
             buf: Vec::with_capacity(256),
+            spans: Vec::new(),
+            record_spans: false,
+            trivia: Vec::new(),
+            full_fidelity: false,
+            fail_fast: false,
+            done: false,
         }
     }
 
+    /// Toggles whether the parser aborts on the first syntax error instead of
+    /// resynchronizing at the next clause terminator.
+    ///
+    /// By default (`false`), a malformed clause contributes at most one error
+    /// to `errs` and the iterator resumes at the following clause, as if by
+    /// panic-mode recovery. When enabled, the iterator records the error and
+    /// then permanently stops, as if at end-of-input.
+    pub fn fail_fast(mut self, yes: bool) -> Self {
+        self.fail_fast = yes;
+        self
+    }
+
+    /// Toggles whether the parser records the `Position` of each `Symbol` as
+    /// it buffers it, retrievable afterward through `last_spans`.
+    ///
+    /// By default (`false`), no positions are recorded and `last_spans`
+    /// always returns an empty slice, so the common case pays no cost for a
+    /// feature it doesn't use. Enable this for diagnostics or tooling that
+    /// needs to point back at a specific subterm instead of just the clause
+    /// as a whole.
+    pub fn spans(mut self, yes: bool) -> Self {
+        self.record_spans = yes;
+        self
+    }
+
+    /// Returns the `Position` of each `Symbol` in the most recently emitted
+    /// `Structure`, aligned 1:1 with its postorder slice.
+    ///
+    /// Empty unless `spans(true)` was set on this parser.
+    pub fn last_spans(&self) -> &[Position] {
+        &self.spans
+    }
+
+    /// Toggles whether the parser retains skipped whitespace and comments as
+    /// [`Trivia`] instead of discarding them, retrievable afterward through
+    /// `last_trivia`.
+    ///
+    /// By default (`false`), space and comment tokens are filtered out by
+    /// the underlying lexer before the parser ever sees them, and the source
+    /// text cannot be reconstructed from the resulting `Structure`s alone.
+    /// Enabling this reconfigures the lexer to report them and has the
+    /// parser skip over them itself, recording each one; this does not
+    /// change the `Structure`s produced, only what `last_trivia` returns
+    /// alongside them. Useful for pretty-printers or linters that need to
+    /// round-trip the input byte-for-byte.
+    ///
+    /// [`Trivia`]: ../repr/struct.Trivia.html
+    pub fn full_fidelity(mut self, yes: bool) -> Self {
+        self.full_fidelity = yes;
+        self.lexer = self.lexer.report_space(yes);
+        self
+    }
+
+    /// Returns the whitespace and comment tokens skipped while reading the
+    /// most recently emitted `Structure`, in source order.
+    ///
+    /// Empty unless `full_fidelity(true)` was set on this parser.
+    pub fn last_trivia(&self) -> &[Trivia<'ctx>] {
+        &self.trivia
+    }
+
     /// Returns a draining iterator over the set of errors.
     pub fn errs(&mut self) -> Drain<SyntaxError> {
         self.errs.drain(0..)
@@ -82,8 +183,14 @@ impl<'ctx, B: BufRead> Iterator for Parser<'ctx, B> {
     type Item = Box<Structure<'ctx>>;
 
     fn next(&mut self) -> Option<Box<Structure<'ctx>>> {
+        if self.done {
+            return None;
+        }
+
         self.vars.clear();
         self.buf.clear();
+        self.spans.clear();
+        self.trivia.clear();
         match self.read(1200) {
             Ok(_) => {
                 if self.buf.len() == 0 {
@@ -91,18 +198,44 @@ impl<'ctx, B: BufRead> Iterator for Parser<'ctx, B> {
                     // Must be at end of input.
                     None
                 } else if let Some(Token::Dot(..)) = self.next_tok() {
+                    if let Some((priority, fixity, names)) = as_op_directive(&self.buf) {
+                        for name in names {
+                            if self.ops.define(priority, fixity, name).is_err() {
+                                let line = self.lexer.line();
+                                let col = self.lexer.col();
+                                let offset = self.lexer.offset();
+                                self.errs.push(SyntaxError::unexpected(line, col, offset, "op/3 directive"));
+                            }
+                        }
+                    }
+                    // Eagerly pull in any trivia trailing the clause's `.`
+                    // (e.g. a same-line comment) so `last_trivia` reflects
+                    // this clause rather than making the caller wait for
+                    // the next `next()` to see it.
+                    self.skip_trivia();
                     let structure = unsafe { struct_from_vec(self.buf.clone()) };
                     Some(structure)
                 } else {
                     let line = self.lexer.line();
                     let col = self.lexer.col();
-                    self.errs.push(SyntaxError::priority_clash(line, col));
+                    let offset = self.lexer.offset();
+                    self.errs.push(SyntaxError::priority_clash(line, col, offset));
+                    if self.fail_fast {
+                        self.done = true;
+                        return None;
+                    }
+                    self.recover();
                     self.next()
                 }
             }
             Err(err) => {
                 self.errs.push(err);
-                return self.next();
+                if self.fail_fast {
+                    self.done = true;
+                    return None;
+                }
+                self.recover();
+                self.next()
             }
         }
     }
@@ -120,7 +253,142 @@ unsafe fn struct_from_vec<'ctx>(vec: Vec<Symbol<'ctx>>) -> Box<Structure<'ctx>>
     mem::transmute(vec.into_boxed_slice())
 }
 
+/// Recognizes a freshly parsed clause's postorder buffer as a
+/// `:- op(Priority, Type, Name)` directive, returning its arguments if so.
+///
+/// `Name` may be a single atom or a proper list of atoms, per ISO `op/3`.
+/// Anything else (a missing `:-`/1 wrapper, a non-`op`/3 argument, a
+/// negative or variable priority, an unrecognized type atom, or a malformed
+/// name) is not a directive and yields `None` rather than an error; the
+/// clause is then just an ordinary term to the parser.
+fn as_op_directive<'ctx>(buf: &[Symbol<'ctx>]) -> Option<(u32, Fixity, Vec<Name<'ctx>>)> {
+    let n = buf.len();
+    if n < 2 {
+        return None;
+    }
+    match buf[n - 1] {
+        Symbol::Funct(1, name) if name.as_str() == ":-" => (),
+        _ => return None,
+    }
+    match buf[n - 2] {
+        Symbol::Funct(3, name) if name.as_str() == "op" => (),
+        _ => return None,
+    }
+
+    let args = arg_spans(buf, n - 2, 3);
+    let (pstart, pend) = args[0];
+    let (tstart, tend) = args[1];
+    let (nstart, nend) = args[2];
+
+    let priority = match (pend - pstart, buf[pstart]) {
+        (1, Symbol::Int(val)) if val >= 0 => val as u32,
+        _ => return None,
+    };
+
+    let fixity = match (tend - tstart, buf[tstart]) {
+        (1, Symbol::Funct(0, name)) => {
+            match name.as_str() {
+                "xf" => Fixity::XF,
+                "yf" => Fixity::YF,
+                "xfx" => Fixity::XFX,
+                "xfy" => Fixity::XFY,
+                "yfx" => Fixity::YFX,
+                "fy" => Fixity::FY,
+                "fx" => Fixity::FX,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let names = atom_list(buf, nstart, nend)?;
+    Some((priority, fixity, names))
+}
+
+/// Returns the `(start, end)` bounds of each of a functor's `arity` children,
+/// in left-to-right order, given that `buf[functor]` is the functor itself.
+fn arg_spans<'ctx>(buf: &[Symbol<'ctx>], functor: usize, arity: usize) -> Vec<(usize, usize)> {
+    let mut spans = Vec::with_capacity(arity);
+    let mut end = functor;
+    for _ in 0..arity {
+        let start = subtree_start(buf, end);
+        spans.push((start, end));
+        end = start;
+    }
+    spans.reverse();
+    spans
+}
+
+/// Returns the start index of the subtree whose root sits at `buf[end - 1]`.
+fn subtree_start<'ctx>(buf: &[Symbol<'ctx>], end: usize) -> usize {
+    let mut pos = end - 1;
+    for _ in 0..buf[pos].arity() {
+        pos = subtree_start(buf, pos);
+    }
+    pos
+}
+
+/// Reads the subtree spanning `buf[start..end]` as a plain atom or a proper
+/// `'.'/2` list of atoms, returning the atoms in list order.
+fn atom_list<'ctx>(buf: &[Symbol<'ctx>], start: usize, end: usize) -> Option<Vec<Name<'ctx>>> {
+    if end - start == 1 {
+        return match buf[start] {
+            Symbol::Funct(0, name) if name.as_str() == "[]" => Some(Vec::new()),
+            Symbol::Funct(0, name) => Some(vec![name]),
+            _ => None,
+        };
+    }
+
+    match buf[end - 1] {
+        Symbol::Funct(2, name) if name.as_str() == "." => {
+            let args = arg_spans(buf, end - 1, 2);
+            let (hstart, hend) = args[0];
+            let (tstart, tend) = args[1];
+            let head = match (hend - hstart, buf[hstart]) {
+                (1, Symbol::Funct(0, name)) => name,
+                _ => return None,
+            };
+            let mut rest = atom_list(buf, tstart, tend)?;
+            rest.insert(0, head);
+            Some(rest)
+        }
+        _ => None,
+    }
+}
+
 impl<'ctx, B: BufRead> Parser<'ctx, B> {
+    /// Pushes a symbol onto the buffer, recording its `Position` alongside it
+    /// when span tracking is enabled via `spans(true)`.
+    fn push_sym(&mut self, sym: Symbol<'ctx>, line: usize, col: usize) {
+        self.buf.push(sym);
+        if self.record_spans {
+            self.spans.push(Position::new(line, col));
+        }
+    }
+
+    /// Consumes and records any run of `Token::Space`/`Token::Comment` at
+    /// the current position as `Trivia`, leaving the first non-trivia token
+    /// peeked.
+    ///
+    /// Ordinarily a no-op: the lexer filters these tokens out before the
+    /// parser ever sees them, unless `full_fidelity(true)` reconfigured it
+    /// to report them.
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek_tok() {
+                Some(&Token::Space(line, col, _, name)) => {
+                    self.trivia.push(Trivia::new(TriviaKind::Space, Position::new(line, col), name));
+                    self.next_tok();
+                }
+                Some(&Token::Comment(line, col, _, name)) => {
+                    self.trivia.push(Trivia::new(TriviaKind::Comment, Position::new(line, col), name));
+                    self.next_tok();
+                }
+                _ => return,
+            }
+        }
+    }
+
     /// Reads the next term up to, but not including, the trailing period.
     ///
     /// The return value is the precedence of the term upon success or
@@ -131,9 +399,13 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
     ///
     /// [1]: https://en.wikipedia.org/wiki/Operator-precedence_parser#Precedence_climbing_method
     fn read(&mut self, max_prec: u32) -> Result<u32> {
-        // Check that we're not at EOF.
-        if self.peek_tok().is_none() {
-            return Ok(0);
+        // Check that we're not at EOF. The lexer reports a single trailing
+        // `Token::Eof` before it starts returning `None`, so both must be
+        // treated as "no more input" here.
+        self.skip_trivia();
+        match self.peek_tok() {
+            None | Some(&Token::Eof(..)) => return Ok(0),
+            _ => (),
         }
 
         // Precedence "climbing" algorithm.
@@ -141,27 +413,31 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
         // Thus all comparisons are the opposite of the pseudo-code.
         let mut prec = self.read_primary(max_prec)?;
         loop {
+            self.skip_trivia();
             match self.peek_tok() {
-                Some(&Token::Bar(.., name)) |
-                Some(&Token::Comma(.., name)) |
-                Some(&Token::Funct(.., name)) => {
-                    match self.ops.get_compatible(name, max_prec, prec) {
+                Some(&Token::Bar(line, col, _, name)) |
+                Some(&Token::Comma(line, col, _, name)) |
+                Some(&Token::Funct(line, col, _, name)) => {
+                    match self.ops.get_compatible(name, prec, max_prec) {
                         None => break,
                         Some(op) => {
                             self.next_tok();
                             match op {
                                 Op::XFY(..) => {
-                                    prec = self.read(op.prec())?;
-                                    self.buf.push(Symbol::Funct(2, name));
+                                    self.read(op.prec())?;
+                                    self.push_sym(Symbol::Funct(2, name), line, col);
                                 }
                                 Op::YFX(..) | Op::XFX(..) => {
-                                    prec = self.read(op.prec() - 1)?;
-                                    self.buf.push(Symbol::Funct(2, name));
+                                    self.read(op.prec() - 1)?;
+                                    self.push_sym(Symbol::Funct(2, name), line, col);
                                 }
                                 _ => {
-                                    self.buf.push(Symbol::Funct(1, name));
+                                    self.push_sym(Symbol::Funct(1, name), line, col);
                                 }
                             }
+                            // The resulting term's own priority is the operator's, regardless of
+                            // how tightly its operands happened to bind.
+                            prec = op.prec();
                         }
                     }
                 }
@@ -178,22 +454,18 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
     /// lists, strings. This step also recursively descends to parse terms
     /// grouped in parens.
     fn read_primary(&mut self, max_prec: u32) -> Result<u32> {
+        self.skip_trivia();
         match self.next_tok() {
-            // Skip spaces and comments.
-            Some(Token::Space(..)) |
-            Some(Token::Comment(..)) => {
-                return self.read_primary(max_prec);
-            }
-
             // Atoms, compounds, and prefix operators.
-            Some(Token::Bar(.., name)) |
-            Some(Token::Comma(.., name)) |
-            Some(Token::Funct(.., name)) => {
+            Some(Token::Bar(line, col, _, name)) |
+            Some(Token::Comma(line, col, _, name)) |
+            Some(Token::Funct(line, col, _, name)) => {
+                self.skip_trivia();
                 match self.peek_tok() {
                     // Compound term
                     Some(&Token::ParenOpen(..)) => {
                         let arity = self.read_args()?;
-                        self.buf.push(Symbol::Funct(arity, name));
+                        self.push_sym(Symbol::Funct(arity, name), line, col);
                         Ok(0)
                     }
 
@@ -201,7 +473,7 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
                     Some(&Token::ParenClose(..)) |
                     Some(&Token::BracketClose(..)) |
                     Some(&Token::BraceClose(..)) => {
-                        self.buf.push(Symbol::Funct(0, name));
+                        self.push_sym(Symbol::Funct(0, name), line, col);
                         Ok(0)
                     }
 
@@ -210,16 +482,16 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
                         match self.ops.get_prefix(name, max_prec) {
                             Some(Op::FX(p, _)) => {
                                 self.read(p - 1)?;
-                                self.buf.push(Symbol::Funct(1, name));
+                                self.push_sym(Symbol::Funct(1, name), line, col);
                                 Ok(p)
                             }
                             Some(Op::FY(p, _)) => {
                                 self.read(p)?;
-                                self.buf.push(Symbol::Funct(1, name));
+                                self.push_sym(Symbol::Funct(1, name), line, col);
                                 Ok(p)
                             }
                             _ => {
-                                self.buf.push(Symbol::Funct(0, name));
+                                self.push_sym(Symbol::Funct(0, name), line, col);
                                 Ok(0)
                             }
                         }
@@ -228,57 +500,145 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
             }
 
             // Strings.
-            Some(Token::Str(.., val)) => {
-                self.buf.push(Symbol::Str(val.as_str()));
+            Some(Token::Str(line, col, _, val)) => {
+                self.push_sym(Symbol::Str(val.as_str()), line, col);
                 Ok(0)
             }
 
             // Variables.
-            Some(Token::Var(.., val)) => {
+            Some(Token::Var(line, col, _, val)) => {
                 match self.vars.iter().position(|name| *name == val) {
                     Some(n) => {
-                        self.buf.push(Symbol::Var(n));
+                        self.push_sym(Symbol::Var(n), line, col);
                         Ok(0)
                     }
                     None => {
                         let n = self.vars.len();
                         self.vars.push(val);
-                        self.buf.push(Symbol::Var(n));
+                        self.push_sym(Symbol::Var(n), line, col);
                         Ok(0)
                     }
                 }
             }
 
             // Numbers.
-            Some(Token::Int(.., val)) => {
-                self.buf.push(Symbol::Int(val));
+            Some(Token::Int(line, col, _, val)) => {
+                self.push_sym(Symbol::Int(val), line, col);
                 Ok(0)
             }
-            Some(Token::Float(.., val)) => {
-                self.buf.push(Symbol::Float(val));
+            Some(Token::Float(line, col, _, val)) => {
+                self.push_sym(Symbol::Float(val), line, col);
                 Ok(0)
             }
 
             // Parens.
-            Some(Token::ParenOpen(line, col)) => {
+            Some(Token::ParenOpen(line, col, offset)) => {
                 self.read(1200)?;
                 match self.next_tok() {
                     Some(Token::ParenClose(..)) => Ok(0),
-                    _ => Err(SyntaxError::unbalanced(line, col, ')')),
+                    _ => Err(SyntaxError::unbalanced(line, col, offset, ')')),
                 }
             }
 
-            // TODO: Lists and braces.
-            Some(Token::BracketOpen(line, col)) => Err(SyntaxError::todo(line, col)),
-            Some(Token::BraceOpen(line, col)) => Err(SyntaxError::todo(line, col)),
+            // Lists.
+            Some(Token::BracketOpen(line, col, offset)) => self.read_list(line, col, offset),
+
+            // Curly-brace terms.
+            Some(Token::BraceOpen(line, col, offset)) => self.read_curly(line, col, offset),
 
             // Syntax errors.
-            Some(Token::ParenClose(line, col)) => Err(SyntaxError::unbalanced(line, col, ')')),
-            Some(Token::BracketClose(line, col)) => Err(SyntaxError::unbalanced(line, col, ']')),
-            Some(Token::BraceClose(line, col)) => Err(SyntaxError::unbalanced(line, col, '}')),
-            Some(Token::Dot(line, col)) => Err(SyntaxError::unexpected(line, col, "period")),
+            Some(Token::ParenClose(line, col, offset)) => Err(SyntaxError::unbalanced(line, col, offset, ')')),
+            Some(Token::BracketClose(line, col, offset)) => Err(SyntaxError::unbalanced(line, col, offset, ']')),
+            Some(Token::BraceClose(line, col, offset)) => Err(SyntaxError::unbalanced(line, col, offset, '}')),
+            Some(Token::Dot(line, col, offset)) => Err(SyntaxError::unexpected(line, col, offset, "period")),
             Some(Token::Err(e)) => Err(e),
-            None => Err(SyntaxError::unexpected(self.lexer.line(), self.lexer.col(), "eof")),
+            Some(Token::Eof(line, col, offset)) => Err(SyntaxError::unexpected(line, col, offset, "eof")),
+
+            Some(Token::Space(..)) | Some(Token::Comment(..)) => {
+                unreachable!("skip_trivia consumes all trivia before this match")
+            }
+
+            None => {
+                let line = self.lexer.line();
+                let col = self.lexer.col();
+                let offset = self.lexer.offset();
+                Err(SyntaxError::unexpected(line, col, offset, "eof"))
+            }
+        }
+    }
+
+    /// Reads a `[...]` list term, starting just after the opening `[` has
+    /// already been consumed.
+    ///
+    /// A list desugars into the usual `'.'/2` cons structure: `[a, b, c]`
+    /// becomes `'.'(a, '.'(b, '.'(c, [])))`, and an explicit tail via `|`
+    /// (`[H | T]`) becomes `'.'(H, T)` instead of terminating in `[]`. The
+    /// empty list `[]` is the 0-ary atom `'[]'`. As in `read_args`, elements
+    /// are read at priority 999 so that commas separate elements rather than
+    /// being parsed as the comma operator.
+    fn read_list(&mut self, line: usize, col: usize, offset: usize) -> Result<u32> {
+        self.skip_trivia();
+        if let Some(&Token::BracketClose(..)) = self.peek_tok() {
+            self.next_tok();
+            let nil = self.lexer.ns().name("[]");
+            self.push_sym(Symbol::Funct(0, nil), line, col);
+            return Ok(0);
+        }
+
+        let mut arity = 0;
+        loop {
+            self.read(999)?;
+            arity += 1;
+            match self.next_tok() {
+                Some(Token::Comma(..)) => continue,
+
+                Some(Token::Bar(..)) => {
+                    self.read(999)?;
+                    match self.next_tok() {
+                        Some(Token::BracketClose(..)) => break,
+                        _ => return Err(SyntaxError::unbalanced(line, col, offset, ']')),
+                    }
+                }
+
+                Some(Token::BracketClose(..)) => {
+                    let nil = self.lexer.ns().name("[]");
+                    self.push_sym(Symbol::Funct(0, nil), line, col);
+                    break;
+                }
+
+                _ => return Err(SyntaxError::unbalanced(line, col, offset, ']')),
+            }
+        }
+
+        let cons = self.lexer.ns().name(".");
+        for _ in 0..arity {
+            self.push_sym(Symbol::Funct(2, cons), line, col);
+        }
+        Ok(0)
+    }
+
+    /// Reads a `{...}` curly-brace term, starting just after the opening `{`
+    /// has already been consumed.
+    ///
+    /// `{Goal}` parses `Goal` at priority 1200 and wraps it as the 1-ary
+    /// structure `'{}'(Goal)`; the empty `{}` is the 0-ary atom `'{}'`.
+    fn read_curly(&mut self, line: usize, col: usize, offset: usize) -> Result<u32> {
+        let curly = self.lexer.ns().name("{}");
+
+        self.skip_trivia();
+        if let Some(&Token::BraceClose(..)) = self.peek_tok() {
+            self.next_tok();
+            self.push_sym(Symbol::Funct(0, curly), line, col);
+            return Ok(0);
+        }
+
+        self.read(1200)?;
+        match self.next_tok() {
+            Some(Token::BraceClose(..)) => {
+                self.push_sym(Symbol::Funct(1, curly), line, col);
+                Ok(0)
+            }
+            _ => Err(SyntaxError::unbalanced(line, col, offset, '}')),
         }
     }
 
@@ -287,8 +647,6 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
     /// Because the precedence of the comma operator is 1000, the precedence of
     /// arguments must be less than 1000 to avoid conflicting. This can be
     /// ensured by wrapping arguments in parens.
-    ///
-    /// TODO: support lists
     fn read_args(&mut self) -> Result<u32> {
         let front = self.next_tok();
         match front {
@@ -296,7 +654,8 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
             None => {
                 let line = self.lexer.line();
                 let col = self.lexer.col();
-                return Err(SyntaxError::unexpected(line, col, "eof"));
+                let offset = self.lexer.offset();
+                return Err(SyntaxError::unexpected(line, col, offset, "eof"));
             }
             _ => panic!("must not call read_args in this context"),
         }
@@ -308,11 +667,15 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
                 Some(Token::ParenClose(..)) => return Ok(arity),
                 Some(Token::Comma(..)) => arity += 1,
 
-                Some(tok) => return Err(SyntaxError::priority_clash(tok.line(), tok.col())),
+                Some(tok) => {
+                    let offset = tok.offset();
+                    return Err(SyntaxError::priority_clash(tok.line(), tok.col(), offset));
+                }
                 None => {
                     let line = self.lexer.line();
                     let col = self.lexer.col();
-                    return Err(SyntaxError::unexpected(line, col, "eof"));
+                    let offset = self.lexer.offset();
+                    return Err(SyntaxError::unexpected(line, col, offset, "eof"));
                 }
             }
         }
@@ -335,6 +698,22 @@ impl<'ctx, B: BufRead> Parser<'ctx, B> {
         }
     }
 
+    /// Discards tokens up to and including the next `Token::Dot`, or until
+    /// end-of-input.
+    ///
+    /// Called after a syntax error so that one malformed clause does not
+    /// prevent the rest of the file from parsing: the next call to `next`
+    /// resumes at the start of the following clause instead of wherever the
+    /// error left off.
+    fn recover(&mut self) {
+        loop {
+            match self.next_tok() {
+                Some(Token::Dot(..)) | None => return,
+                _ => (),
+            }
+        }
+    }
+
     /// Get the next token from the lexer.
     ///
     /// Calling `self.lexer.next()` directly outside of this or `peek_tok`
@@ -376,7 +755,7 @@ mod test {
                       Funct(1, ns.name("-"))];
         let st = unsafe { struct_from_vec(st) };
 
-        let mut parser = Parser::new(pl.as_bytes(), &ns, &ops);
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
         assert_eq!(parser.errs().count(), 0);
         assert_eq!(parser.next(), Some(st));
     }
@@ -396,7 +775,7 @@ mod test {
                       Funct(2, ns.name("+"))];
         let st = unsafe { struct_from_vec(st) };
 
-        let mut parser = Parser::new(pl.as_bytes(), &ns, &ops);
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
         assert_eq!(parser.next(), Some(st));
         assert_eq!(parser.errs().count(), 0);
     }
@@ -406,23 +785,22 @@ mod test {
         let ns = NameSpace::new();
         let ops = OpTable::default(&ns);
 
-        // TODO: update to list syntax
-        let pl = "member(H, list(H,T)).\n\
-                  member(X, list(_,T)) :- member(X, T).\n";
+        let pl = "member(H, [H|T]).\n\
+                  member(X, [_|T]) :- member(X, T).\n";
 
         let first =
-            &[Var(0), Var(0), Var(1), Funct(2, ns.name("list")), Funct(2, ns.name("member"))];
+            &[Var(0), Var(0), Var(1), Funct(2, ns.name(".")), Funct(2, ns.name("member"))];
         let second = &[Var(0),
                        Var(1),
                        Var(2),
-                       Funct(2, ns.name("list")),
+                       Funct(2, ns.name(".")),
                        Funct(2, ns.name("member")),
                        Var(0),
                        Var(2),
                        Funct(2, ns.name("member")),
                        Funct(2, ns.name(":-"))];
 
-        let mut parser = Parser::new(pl.as_bytes(), &ns, &ops);
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
 
         assert_eq!(parser.next().unwrap().as_slice(), first);
         assert_eq!(parser.errs().count(), 0);
@@ -430,4 +808,290 @@ mod test {
         assert_eq!(parser.next().unwrap().as_slice(), second);
         assert_eq!(parser.errs().count(), 0);
     }
+
+    #[test]
+    fn right_associative_chain() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "a :- b, c, d.\n";
+        let st = vec![Funct(0, ns.name("a")),
+                      Funct(0, ns.name("b")),
+                      Funct(0, ns.name("c")),
+                      Funct(0, ns.name("d")),
+                      Funct(2, ns.name(",")),
+                      Funct(2, ns.name(",")),
+                      Funct(2, ns.name(":-"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn non_associative_chain_is_a_priority_clash() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        // `=` is xfx, so `a = b = c` is not a valid term without parens; the
+        // whole malformed clause is discarded and the parser resynchronizes
+        // at the following `.` to recover the `foo` clause.
+        let pl = "a = b = c.\nfoo.\n";
+        let st = vec![Funct(0, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 1);
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn recovers_at_the_next_dot_after_a_syntax_error() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        // The stray `)` after `foo(X)` is a syntax error, but the parser
+        // should resynchronize at the following `.` and still recover the
+        // `bar` clause rather than aborting or eating it as part of the
+        // error recovery.
+        let pl = "foo(X)) .\nbar.\n";
+        let st = vec![Funct(0, ns.name("bar"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 1);
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn fail_fast_stops_at_the_first_syntax_error() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        // Same input as `recovers_at_the_next_dot_after_a_syntax_error`, but
+        // with fail-fast enabled the parser must not resynchronize and
+        // recover the following `bar` clause.
+        let pl = "foo(X)) .\nbar.\n";
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops).fail_fast(true);
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.errs().count(), 1);
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn list_desugars_into_cons_cells() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "foo([a, b, c]).\n";
+        let st = vec![Funct(0, ns.name("a")),
+                      Funct(0, ns.name("b")),
+                      Funct(0, ns.name("c")),
+                      Funct(0, ns.name("[]")),
+                      Funct(2, ns.name(".")),
+                      Funct(2, ns.name(".")),
+                      Funct(2, ns.name(".")),
+                      Funct(1, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn list_with_explicit_tail() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "foo([H | T]).\n";
+        let st = vec![Var(0),
+                      Var(1),
+                      Funct(2, ns.name(".")),
+                      Funct(1, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn empty_list_is_the_nil_atom() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "foo([]).\n";
+        let st = vec![Funct(0, ns.name("[]")),
+                      Funct(1, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn curly_term_wraps_the_inner_goal() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "foo({a, b}).\n";
+        let st = vec![Funct(0, ns.name("a")),
+                      Funct(0, ns.name("b")),
+                      Funct(2, ns.name(",")),
+                      Funct(1, ns.name("{}")),
+                      Funct(1, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn empty_curly_term_is_the_brace_atom() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "foo({}).\n";
+        let st = vec![Funct(0, ns.name("{}")),
+                      Funct(1, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn curly_terms_nest() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "{{a}}.\n";
+        let st = vec![Funct(0, ns.name("a")),
+                      Funct(1, ns.name("{}")),
+                      Funct(1, ns.name("{}"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn op_directive_is_in_effect_for_later_clauses() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = ":- op(700, xfx, ===).\n\
+                  a === b.\n";
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+
+        // The directive clause parses like any other term.
+        assert!(parser.next().is_some());
+        assert_eq!(parser.errs().count(), 0);
+
+        // `===` is now a registered infix operator, so `a === b` parses as a
+        // single compound rather than failing or being read as three atoms.
+        let st = vec![Funct(0, ns.name("a")), Funct(0, ns.name("b")), Funct(2, ns.name("==="))];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(parser.next(), Some(st));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn op_directive_accepts_a_list_of_names() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = ":- op(200, xfy, [foo, bar]).\n\
+                  a foo b.\n\
+                  a bar b.\n";
+
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert!(parser.next().is_some());
+        assert_eq!(parser.errs().count(), 0);
+
+        let first = vec![Funct(0, ns.name("a")), Funct(0, ns.name("b")), Funct(2, ns.name("foo"))];
+        let first = unsafe { struct_from_vec(first) };
+        assert_eq!(parser.next(), Some(first));
+        assert_eq!(parser.errs().count(), 0);
+
+        let second = vec![Funct(0, ns.name("a")), Funct(0, ns.name("b")), Funct(2, ns.name("bar"))];
+        let second = unsafe { struct_from_vec(second) };
+        assert_eq!(parser.next(), Some(second));
+        assert_eq!(parser.errs().count(), 0);
+    }
+
+    #[test]
+    fn spans_are_empty_unless_requested() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "foo(a, b).\n";
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert!(parser.next().is_some());
+        assert!(parser.last_spans().is_empty());
+    }
+
+    #[test]
+    fn spans_track_each_symbols_position() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        // col: 1234567890
+        let pl = "foo(a, b).\n";
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops).spans(true);
+        assert!(parser.next().is_some());
+
+        // Symbols are pushed in postorder, so the arguments precede the functor.
+        let spans = [Position::new(1, 5), Position::new(1, 8), Position::new(1, 1)];
+        assert_eq!(parser.last_spans(), &spans[..]);
+    }
+
+    #[test]
+    fn trivia_is_empty_unless_requested() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        let pl = "foo(X). % comment\n";
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops);
+        assert!(parser.next().is_some());
+        assert!(parser.last_trivia().is_empty());
+    }
+
+    #[test]
+    fn full_fidelity_mode_recovers_a_trailing_comment() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        // col: 123456789012345678
+        let pl = "foo(X). % comment\n";
+        let mut parser = Parser::new(pl.as_bytes(), &ns, ops).full_fidelity(true);
+        assert!(parser.next().is_some());
+
+        // The space before the comment, the comment itself, and the
+        // trailing newline are all retained, in source order, alongside the
+        // clause they trail.
+        let trivia = parser.last_trivia();
+        assert_eq!(trivia.len(), 3);
+        assert_eq!(trivia[0].kind(), TriviaKind::Space);
+        assert_eq!(trivia[0].pos(), Position::new(1, 8));
+        assert_eq!(trivia[0].text().as_str(), " ");
+        assert_eq!(trivia[1].kind(), TriviaKind::Comment);
+        assert_eq!(trivia[1].pos(), Position::new(1, 9));
+        assert_eq!(trivia[1].text().as_str(), "% comment");
+        assert_eq!(trivia[2].kind(), TriviaKind::Space);
+        assert_eq!(trivia[2].pos(), Position::new(1, 18));
+
+        assert_eq!(parser.next(), None);
+        assert!(parser.last_trivia().is_empty());
+    }
 }
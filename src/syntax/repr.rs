@@ -38,6 +38,98 @@ pub enum Symbol<'ns> {
 #[derive(PartialEq)]
 pub struct Structure<'ns>([Symbol<'ns>]);
 
+/// A 1-based line and column into the source text.
+///
+/// A `Position` identifies where a single `Symbol` came from, so that a
+/// `Structure`'s symbols can be traced back to specific source locations for
+/// diagnostics. `Position::eof()` stands in for the symbols that have no real
+/// source location, such as the end of input.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq)]
+pub struct Position {
+    line: u32,
+    pos: u32,
+}
+
+impl Position {
+    /// Constructs a position from a 1-based line and column.
+    pub fn new(line: usize, pos: usize) -> Position {
+        Position {
+            line: line as u32,
+            pos: pos as u32,
+        }
+    }
+
+    /// The position standing in for the absence of a real source location.
+    pub fn eof() -> Position {
+        Position { line: 0, pos: 0 }
+    }
+
+    /// The 1-based line number, or `0` for `Position::eof()`.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The 1-based column, or `0` for `Position::eof()`.
+    pub fn pos(&self) -> u32 {
+        self.pos
+    }
+}
+
+/// Whether a piece of retained [`Trivia`] is whitespace or a comment.
+///
+/// [`Trivia`]: ./struct.Trivia.html
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq)]
+pub enum TriviaKind {
+    Space,
+    Comment,
+}
+
+/// A whitespace run or comment skipped while reading a `Structure`.
+///
+/// Ordinarily a parser discards whitespace and comments outright, since the
+/// `Symbol`s of a `Structure` carry no use for them. A parser running in
+/// full-fidelity mode instead retains them as `Trivia`, in source order,
+/// so that a pretty-printer or linter can reconstruct the original text
+/// byte-for-byte.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq)]
+pub struct Trivia<'ns> {
+    kind: TriviaKind,
+    pos: Position,
+    text: Name<'ns>,
+}
+
+impl<'ns> Trivia<'ns> {
+    /// Constructs a piece of trivia from its kind, position, and text.
+    pub fn new(kind: TriviaKind, pos: Position, text: Name<'ns>) -> Trivia<'ns> {
+        Trivia {
+            kind: kind,
+            pos: pos,
+            text: text,
+        }
+    }
+
+    /// Whether this is whitespace or a comment.
+    pub fn kind(&self) -> TriviaKind {
+        self.kind
+    }
+
+    /// The position at which the trivia starts.
+    pub fn pos(&self) -> Position {
+        self.pos
+    }
+
+    /// The trivia's exact source text.
+    pub fn text(&self) -> Name<'ns> {
+        self.text
+    }
+}
+
 // Structure
 // --------------------------------------------------
 
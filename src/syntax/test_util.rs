@@ -0,0 +1,14 @@
+//! Test-only helpers shared by `syntax`'s own test suites and by other
+//! modules' tests that need to build a `Structure` by hand.
+
+use std::mem;
+
+use syntax::repr::{Structure, Symbol};
+
+/// Converts a vector of symbols into a structure.
+///
+/// See `parser::struct_from_vec`: safe as long as `vec` is a valid postorder
+/// buffer.
+pub unsafe fn struct_from_vec<'ns>(vec: Vec<Symbol<'ns>>) -> Box<Structure<'ns>> {
+    mem::transmute(vec.into_boxed_slice())
+}
@@ -0,0 +1,296 @@
+//! Visitor and fold traversal over [`Structure`] trees.
+//!
+//! A `Structure` stores its tree of `Symbol`s as a flat postorder (postfix)
+//! array: every compound's children precede it, in left-to-right order, and
+//! its arity says how many of the preceding symbols belong to it. Decoding
+//! this by hand means re-deriving each symbol's children from its arity
+//! every time, which this module exists to spare downstream code from.
+//!
+//! [`Visitor`] walks a `Structure` read-only, calling one hook per kind of
+//! `Symbol`, in postorder (children before parents) - the same order the
+//! buffer itself is built in. [`Fold`] is the rewriting counterpart: its
+//! hooks return replacement fragments instead, and `fold` rebuilds a new
+//! `Structure` bottom-up from them, which is how a term gets its variables
+//! renamed apart or its bindings substituted in.
+//!
+//! [`Structure`]: ../repr/struct.Structure.html
+//! [`Visitor`]: trait.Visitor.html
+//! [`Fold`]: trait.Fold.html
+
+use std::mem;
+
+use syntax::namespace::Name;
+use syntax::repr::{Structure, Symbol};
+
+/// Walks a `Structure` in postorder, calling one hook per kind of `Symbol`
+/// encountered.
+///
+/// Every hook defaults to doing nothing, so a visitor need only override the
+/// ones it cares about. The default `visit` method does the postfix decode
+/// itself and drives the traversal; it is the only method most callers need.
+pub trait Visitor<'ns> {
+    fn visit_funct(&mut self, _arity: u32, _name: Name<'ns>) {}
+    fn visit_list(&mut self, _partial: bool, _arity: u32) {}
+    fn visit_var(&mut self, _id: usize) {}
+    fn visit_int(&mut self, _val: i64) {}
+    fn visit_float(&mut self, _val: f64) {}
+    fn visit_str(&mut self, _val: &'ns str) {}
+
+    /// Walks `st`'s symbols in postorder - every symbol's children, then the
+    /// symbol itself - calling the hook matching each one.
+    fn visit(&mut self, st: &Structure<'ns>) {
+        let buf = st.as_slice();
+        if !buf.is_empty() {
+            walk(self, buf, buf.len());
+        }
+    }
+}
+
+/// Visits the subtree of `buf` ending just before index `end` (i.e. rooted
+/// at `buf[end - 1]`), children first.
+fn walk<'ns, V: Visitor<'ns> + ?Sized>(visitor: &mut V, buf: &[Symbol<'ns>], end: usize) {
+    let root = end - 1;
+    let sym = buf[root];
+
+    for child_end in child_ends(buf, root) {
+        walk(visitor, buf, child_end);
+    }
+
+    match sym {
+        Symbol::Funct(arity, name) => visitor.visit_funct(arity, name),
+        Symbol::List(partial, arity) => visitor.visit_list(partial, arity),
+        Symbol::Var(id) => visitor.visit_var(id),
+        Symbol::Int(val) => visitor.visit_int(val),
+        Symbol::Float(val) => visitor.visit_float(val),
+        Symbol::Str(val) => visitor.visit_str(val),
+    }
+}
+
+/// A `Visitor` variant whose hooks return replacement subterms - as fresh
+/// postorder fragments - instead of nothing, so that `fold` can rebuild a
+/// new `Structure` bottom-up out of them.
+///
+/// Every hook defaults to re-emitting the symbol it was given unchanged, so
+/// a fold that only needs to, say, substitute variables can override just
+/// `fold_var` and leave the rest as an identity transform.
+pub trait Fold<'ns> {
+    fn fold_funct(&mut self, arity: u32, name: Name<'ns>, args: Vec<Symbol<'ns>>) -> Vec<Symbol<'ns>> {
+        let mut out = args;
+        out.push(Symbol::Funct(arity, name));
+        out
+    }
+
+    fn fold_list(&mut self, partial: bool, arity: u32, args: Vec<Symbol<'ns>>) -> Vec<Symbol<'ns>> {
+        let mut out = args;
+        out.push(Symbol::List(partial, arity));
+        out
+    }
+
+    fn fold_var(&mut self, id: usize) -> Vec<Symbol<'ns>> {
+        vec![Symbol::Var(id)]
+    }
+
+    fn fold_int(&mut self, val: i64) -> Vec<Symbol<'ns>> {
+        vec![Symbol::Int(val)]
+    }
+
+    fn fold_float(&mut self, val: f64) -> Vec<Symbol<'ns>> {
+        vec![Symbol::Float(val)]
+    }
+
+    fn fold_str(&mut self, val: &'ns str) -> Vec<Symbol<'ns>> {
+        vec![Symbol::Str(val)]
+    }
+
+    /// Rebuilds `st` bottom-up: every symbol's children are folded first,
+    /// and the resulting fragments are passed to the hook matching that
+    /// symbol, whose return value stands in for it in the new `Structure`.
+    fn fold(&mut self, st: &Structure<'ns>) -> Box<Structure<'ns>> {
+        let buf = st.as_slice();
+        let out = if buf.is_empty() {
+            Vec::new()
+        } else {
+            fold_walk(self, buf, buf.len())
+        };
+        unsafe { struct_from_vec(out) }
+    }
+}
+
+/// Folds the subtree of `buf` ending just before index `end`, returning its
+/// replacement as a fresh postorder fragment.
+fn fold_walk<'ns, F: Fold<'ns> + ?Sized>(folder: &mut F, buf: &[Symbol<'ns>], end: usize) -> Vec<Symbol<'ns>> {
+    let root = end - 1;
+    let sym = buf[root];
+
+    let mut args = Vec::new();
+    for child_end in child_ends(buf, root) {
+        args.extend(fold_walk(folder, buf, child_end));
+    }
+
+    match sym {
+        Symbol::Funct(arity, name) => folder.fold_funct(arity, name, args),
+        Symbol::List(partial, arity) => folder.fold_list(partial, arity, args),
+        Symbol::Var(id) => folder.fold_var(id),
+        Symbol::Int(val) => folder.fold_int(val),
+        Symbol::Float(val) => folder.fold_float(val),
+        Symbol::Str(val) => folder.fold_str(val),
+    }
+}
+
+/// Returns the end index (one past the last symbol) of each child of
+/// `buf[root]`, in left-to-right order.
+///
+/// Children sit directly before their parent, so the rightmost child is
+/// found first by walking backward from `root`; the result is reversed
+/// before returning so callers can recurse in source order.
+fn child_ends<'ns>(buf: &[Symbol<'ns>], root: usize) -> Vec<usize> {
+    let arity = buf[root].arity();
+    let mut ends = Vec::with_capacity(arity);
+    let mut end = root;
+    for _ in 0..arity {
+        ends.push(end);
+        end = subtree_start(buf, end);
+    }
+    ends.reverse();
+    ends
+}
+
+/// Returns the start index of the subtree whose root is `buf[end - 1]`.
+fn subtree_start<'ns>(buf: &[Symbol<'ns>], end: usize) -> usize {
+    let mut pos = end - 1;
+    for _ in 0..buf[pos].arity() {
+        pos = subtree_start(buf, pos);
+    }
+    pos
+}
+
+/// Returns the sub-slice of `buf` spanning the `arg`-th child (0-based,
+/// left-to-right) of the compound rooted at `buf[root]`, so that a visitor
+/// can recurse into a single argument by slice instead of rebuilding it.
+///
+/// Panics if `arg` is not less than `buf[root]`'s arity.
+pub fn arg_slice<'a, 'ns>(buf: &'a [Symbol<'ns>], root: usize, arg: usize) -> &'a [Symbol<'ns>] {
+    let arity = buf[root].arity();
+    assert!(arg < arity, "argument index out of range");
+
+    let end = child_ends(buf, root)[arg];
+    let start = subtree_start(buf, end);
+    &buf[start..end]
+}
+
+/// Converts a vector of symbols into a structure.
+///
+/// See `parser::struct_from_vec`: safe as long as `vec` is a valid
+/// postorder buffer, which every `fold_*` hook above is responsible for
+/// maintaining by construction.
+unsafe fn struct_from_vec<'ns>(vec: Vec<Symbol<'ns>>) -> Box<Structure<'ns>> {
+    mem::transmute(vec.into_boxed_slice())
+}
+
+// Tests
+// --------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use syntax::namespace::NameSpace;
+    use syntax::repr::Symbol::*;
+
+    #[derive(Default)]
+    struct Counts {
+        functs: usize,
+        vars: usize,
+        ints: usize,
+        order: Vec<&'static str>,
+    }
+
+    impl<'ns> Visitor<'ns> for Counts {
+        fn visit_funct(&mut self, _arity: u32, _name: Name<'ns>) {
+            self.functs += 1;
+            self.order.push("funct");
+        }
+        fn visit_var(&mut self, _id: usize) {
+            self.vars += 1;
+            self.order.push("var");
+        }
+        fn visit_int(&mut self, _val: i64) {
+            self.ints += 1;
+            self.order.push("int");
+        }
+    }
+
+    #[test]
+    fn visit_reaches_every_symbol_in_postorder() {
+        let ns = NameSpace::new();
+        // foo(X, 1)
+        let st = vec![Var(0), Int(1), Funct(2, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut counts = Counts::default();
+        counts.visit(&st);
+        assert_eq!(counts.functs, 1);
+        assert_eq!(counts.vars, 1);
+        assert_eq!(counts.ints, 1);
+        assert_eq!(counts.order, vec!["var", "int", "funct"]);
+    }
+
+    struct Renumber;
+
+    impl<'ns> Fold<'ns> for Renumber {
+        fn fold_var(&mut self, id: usize) -> Vec<Symbol<'ns>> {
+            vec![Symbol::Var(id + 10)]
+        }
+    }
+
+    #[test]
+    fn fold_rebuilds_the_structure_with_substitutions_applied() {
+        let ns = NameSpace::new();
+        // foo(X, Y)
+        let st = vec![Var(0), Var(1), Funct(2, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let renumbered = Renumber.fold(&st);
+        let expected = vec![Var(10), Var(11), Funct(2, ns.name("foo"))];
+        let expected = unsafe { struct_from_vec(expected) };
+        assert_eq!(renumbered, expected);
+    }
+
+    struct ReplaceFirstVar<'ns>(Vec<Symbol<'ns>>);
+
+    impl<'ns> Fold<'ns> for ReplaceFirstVar<'ns> {
+        fn fold_var(&mut self, id: usize) -> Vec<Symbol<'ns>> {
+            if id == 0 {
+                self.0.clone()
+            } else {
+                vec![Symbol::Var(id)]
+            }
+        }
+    }
+
+    #[test]
+    fn fold_can_substitute_a_variable_with_a_whole_subterm() {
+        let ns = NameSpace::new();
+        // foo(X, Y), with X bound to bar(1)
+        let st = vec![Var(0), Var(1), Funct(2, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+        let binding = vec![Int(1), Funct(1, ns.name("bar"))];
+
+        let substituted = ReplaceFirstVar(binding).fold(&st);
+        let expected = vec![Int(1), Funct(1, ns.name("bar")), Var(1), Funct(2, ns.name("foo"))];
+        let expected = unsafe { struct_from_vec(expected) };
+        assert_eq!(substituted, expected);
+    }
+
+    #[test]
+    fn arg_slice_spans_a_single_argument() {
+        let ns = NameSpace::new();
+        // foo(bar(1), X)
+        let st = vec![Int(1), Funct(1, ns.name("bar")), Var(0), Funct(2, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+        let buf = st.as_slice();
+        let root = buf.len() - 1;
+
+        assert_eq!(arg_slice(buf, root, 0), &[Int(1), Funct(1, ns.name("bar"))]);
+        assert_eq!(arg_slice(buf, root, 1), &[Var(0)]);
+    }
+}
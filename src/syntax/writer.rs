@@ -0,0 +1,430 @@
+//! Writes `Structure`s back out as Prolog source text.
+//!
+//! [`write`] is the inverse of [`Parser`]: given a `Structure` and the
+//! `OpTable` used to parse it, it reconstructs syntactically valid text,
+//! rendering operators infix/prefix/postfix where the `OpTable` recognizes
+//! them and falling back to canonical `functor(arg1, arg2)` notation
+//! otherwise. The goal is round-trip fidelity: parsing the output should
+//! yield a `Structure` equal to the one that was written.
+//!
+//! [`Parser`]: ../parser/struct.Parser.html
+//! [`write`]: fn.write.html
+
+use std::io::{self, Write};
+
+use syntax::namespace::Name;
+use syntax::operators::{Op, OpTable};
+use syntax::repr::{Structure, Symbol};
+
+/// Writes `st` as syntactically valid Prolog text, using `ops` to decide
+/// which functors print as operators.
+///
+/// `var_names[n]` is used to name variable `n` if present and non-empty;
+/// otherwise the variable is named `_G<n>`. Lists (however a `Structure`
+/// spells them; see [`Symbol::List`]) print as `[a, b | Tail]`, and atoms
+/// that would not read back as the same atom unquoted are single-quoted.
+///
+/// [`Symbol::List`]: ../repr/enum.Symbol.html#variant.List
+pub fn write<W: Write>(st: &Structure, ops: &OpTable, var_names: &[&str], out: &mut W) -> io::Result<()> {
+    let mut stack: Vec<Rendered> = Vec::with_capacity(st.len());
+
+    for sym in st.as_slice() {
+        let term = match *sym {
+            Symbol::Var(n) => {
+                match var_names.get(n) {
+                    Some(name) if !name.is_empty() => Term::atom(name.to_string()),
+                    _ => Term::atom(format!("_G{}", n)),
+                }
+            }
+            Symbol::Int(val) => Term::atom(val.to_string()),
+            Symbol::Float(val) => Term::atom(format_float(val)),
+            Symbol::Str(val) => Term::atom(quote_str(val)),
+            Symbol::Funct(0, name) if name.as_str() == "[]" => Term::nil(),
+            Symbol::Funct(0, name) => Term::atom(quote_atom(name.as_str())),
+            Symbol::Funct(2, name) if name.as_str() == "." => {
+                let mut args = pop_args(&mut stack, 2);
+                let tail = args.pop().unwrap();
+                let head = args.pop().unwrap();
+                cons(head, tail)
+            }
+            Symbol::Funct(arity, name) => {
+                let args = pop_args(&mut stack, arity as usize);
+                write_compound(name, arity, args, ops)
+            }
+            Symbol::List(true, 0) => Term::nil(),
+            Symbol::List(..) => {
+                let mut args = pop_args(&mut stack, 2);
+                let tail = args.pop().unwrap();
+                let head = args.pop().unwrap();
+                cons(head, tail)
+            }
+        };
+        stack.push(term);
+    }
+
+    let result = stack.pop().expect("a Structure always has at least one symbol");
+    write!(out, "{}", result.render(1200))
+}
+
+/// A subterm rendered so far. `Rendered` pairs it with the priority of its
+/// own outermost operator (`0` for atoms, compounds, and canonical-notation
+/// terms) so a surrounding operator can tell whether it needs parenthesizing.
+enum Term {
+    Atom(String),
+    Nil,
+    /// A (possibly still growing) list: elements rendered so far, and
+    /// whatever sits in the final cons cell's tail position once a
+    /// non-cons term is reached.
+    List(Vec<String>, Tail),
+}
+
+enum Tail {
+    Nil,
+    Other(String),
+}
+
+struct Rendered {
+    value: Term,
+    prec: u32,
+}
+
+impl Term {
+    fn atom(text: String) -> Rendered {
+        Rendered { value: Term::Atom(text), prec: 0 }
+    }
+
+    fn nil() -> Rendered {
+        Rendered { value: Term::Nil, prec: 0 }
+    }
+}
+
+impl Rendered {
+    /// Renders this term as it should appear in a context with the given
+    /// maximum priority (a function argument or list element is `999`;
+    /// an operator's operand is whatever its fixity demands), adding
+    /// parens if its own priority would otherwise read wrong.
+    fn render(&self, max_prec: u32) -> String {
+        let text = match self.value {
+            Term::Nil => "[]".to_string(),
+            Term::Atom(ref s) => s.clone(),
+            Term::List(ref elems, ref tail) => {
+                match *tail {
+                    Tail::Nil => format!("[{}]", elems.join(", ")),
+                    Tail::Other(ref t) => format!("[{} | {}]", elems.join(", "), t),
+                }
+            }
+        };
+        if self.prec > max_prec {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+}
+
+/// Pops `arity` already-rendered subterms off `stack`, in left-to-right
+/// (original argument) order.
+fn pop_args(stack: &mut Vec<Rendered>, arity: usize) -> Vec<Rendered> {
+    let mut args = Vec::with_capacity(arity);
+    for _ in 0..arity {
+        args.push(stack.pop().expect("a Structure's arity must match its buffered subterms"));
+    }
+    args.reverse();
+    args
+}
+
+/// Prepends a freshly read list element onto an already-rendered tail,
+/// merging into the tail's own element run rather than nesting another
+/// `[... | [...]]`.
+fn cons(head: Rendered, tail: Rendered) -> Rendered {
+    let head_text = head.render(999);
+    let value = match tail.value {
+        Term::Nil => Term::List(vec![head_text], Tail::Nil),
+        Term::List(mut elems, t) => {
+            elems.insert(0, head_text);
+            Term::List(elems, t)
+        }
+        Term::Atom(s) => Term::List(vec![head_text], Tail::Other(wrap(s, tail.prec, 999))),
+    };
+    Rendered { value: value, prec: 0 }
+}
+
+/// Parenthesizes `text` if `prec` exceeds `max_prec`, mirroring `Rendered::render`
+/// for a value that's already been taken apart.
+fn wrap(text: String, prec: u32, max_prec: u32) -> String {
+    if prec > max_prec {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+/// Renders a compound term of the given `name`/`arity`, as an operator if
+/// `ops` recognizes one matching, or in canonical `name(arg1, arg2)`
+/// notation otherwise.
+fn write_compound<'ns>(name: Name<'ns>, arity: u32, mut args: Vec<Rendered>, ops: &OpTable<'ns>) -> Rendered {
+    if arity == 1 {
+        if let Some(op) = ops.get_prefix(name, 1200) {
+            let (p, max_arg) = prefix_bounds(op);
+            let arg = args.pop().unwrap().render(max_arg);
+            let text = format!("{} {}", quote_atom(name.as_str()), arg);
+            return Rendered { value: Term::Atom(text), prec: p };
+        }
+        if let Some(op) = ops.get_postfix(name, 1200) {
+            let (p, max_arg) = postfix_bounds(op);
+            let arg = args.pop().unwrap().render(max_arg);
+            let text = format!("{} {}", arg, quote_atom(name.as_str()));
+            return Rendered { value: Term::Atom(text), prec: p };
+        }
+    } else if arity == 2 {
+        if let Some(op) = ops.get_infix(name, 1200) {
+            let (p, max_left, max_right) = infix_bounds(op);
+            let rhs = args.pop().unwrap().render(max_right);
+            let lhs = args.pop().unwrap().render(max_left);
+            let text = format!("{} {} {}", lhs, quote_atom(name.as_str()), rhs);
+            return Rendered { value: Term::Atom(text), prec: p };
+        }
+    }
+
+    let rendered: Vec<String> = args.iter().map(|a| a.render(999)).collect();
+    let text = format!("{}({})", quote_atom(name.as_str()), rendered.join(", "));
+    Rendered { value: Term::Atom(text), prec: 0 }
+}
+
+/// The `(own priority, max operand priority)` pair for a prefix operator.
+fn prefix_bounds(op: Op) -> (u32, u32) {
+    match op {
+        Op::FX(p, _) => (p, p - 1),
+        Op::FY(p, _) => (p, p),
+        _ => unreachable!("get_prefix only returns FX/FY"),
+    }
+}
+
+/// The `(own priority, max operand priority)` pair for a postfix operator.
+fn postfix_bounds(op: Op) -> (u32, u32) {
+    match op {
+        Op::XF(p, _) => (p, p - 1),
+        Op::YF(p, _) => (p, p),
+        _ => unreachable!("get_postfix only returns XF/YF"),
+    }
+}
+
+/// The `(own priority, max left operand priority, max right operand priority)`
+/// triple for an infix operator.
+fn infix_bounds(op: Op) -> (u32, u32, u32) {
+    match op {
+        Op::XFX(p, _) => (p, p - 1, p - 1),
+        Op::XFY(p, _) => (p, p - 1, p),
+        Op::YFX(p, _) => (p, p, p - 1),
+        _ => unreachable!("get_infix only returns XFX/XFY/YFX"),
+    }
+}
+
+/// Formats an `f64` so it always reads back as a float rather than an int:
+/// Rust's own `Display` for a whole number like `1.0` omits the decimal
+/// point, which the lexer would otherwise re-read as `Token::Int`.
+fn format_float(val: f64) -> String {
+    let mut s = val.to_string();
+    if !s.contains('.') && !s.contains('e') && !s.contains('E') {
+        s.push_str(".0");
+    }
+    s
+}
+
+/// Quotes a string literal's contents in double quotes, escaping the
+/// characters that would otherwise end the literal early or be misread.
+fn quote_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders an atom's name, single-quoting it if it wouldn't read back as
+/// the same atom unquoted.
+fn quote_atom(name: &str) -> String {
+    if is_unquoted_atom(name) {
+        return name.to_string();
+    }
+
+    let mut out = String::with_capacity(name.len() + 2);
+    out.push('\'');
+    for ch in name.chars() {
+        match ch {
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// Whether `name` lexes back to itself as a bare `Token::Funct` without
+/// quoting: either a lowercase-initial word, a run of purely symbolic
+/// characters, or one of the special atoms `[]`/`{}`/`!`.
+fn is_unquoted_atom(name: &str) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+    if name == "[]" || name == "{}" || name == "!" {
+        return true;
+    }
+
+    let mut chars = name.chars();
+    let first = chars.next().unwrap();
+    if first == '_' {
+        return false; // would lex as a variable, not a bare atom
+    }
+    if first.is_lowercase() {
+        return name.chars().all(|ch| ch.is_alphanumeric() || ch == '_');
+    }
+
+    name.chars().all(is_symbolic_char)
+}
+
+/// A conservative approximation of the lexer's symbolic functor characters:
+/// not alphanumeric, whitespace, or control, and not one of `,`, `.`, `|`,
+/// quotes, or the bracketing delimiters, all of which always end a bare
+/// symbolic atom early. May reject a few characters the lexer would in fact
+/// accept; that only causes harmless over-quoting, never a bad round-trip.
+fn is_symbolic_char(ch: char) -> bool {
+    !ch.is_alphanumeric() && !ch.is_whitespace() && !ch.is_control() && !"'\",.|%{[()]}".contains(ch)
+}
+
+// Tests
+// --------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use syntax::namespace::NameSpace;
+    use syntax::operators::OpTable;
+    use syntax::repr::Symbol::*;
+    use syntax::test_util::struct_from_vec;
+
+    fn render(st: &Structure, ops: &OpTable) -> String {
+        let mut buf = Vec::new();
+        write(st, ops, &[], &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn canonical_compound() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let st = vec![Int(123), Float(456.789), Funct(2, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "foo(123, 456.789)");
+    }
+
+    #[test]
+    fn infix_operators_print_without_redundant_parens() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        // a+b*c
+        let st = vec![Funct(0, ns.name("a")),
+                      Funct(0, ns.name("b")),
+                      Funct(0, ns.name("c")),
+                      Funct(2, ns.name("*")),
+                      Funct(2, ns.name("+"))];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "a + b * c");
+    }
+
+    #[test]
+    fn infix_operators_keep_necessary_parens() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+
+        // (a+b)*c
+        let st = vec![Funct(0, ns.name("a")),
+                      Funct(0, ns.name("b")),
+                      Funct(2, ns.name("+")),
+                      Funct(0, ns.name("c")),
+                      Funct(2, ns.name("*"))];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "(a + b) * c");
+    }
+
+    #[test]
+    fn prefix_operator() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let st = vec![Funct(0, ns.name("a")), Funct(1, ns.name("-"))];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "- a");
+    }
+
+    #[test]
+    fn proper_list() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let cons = ns.name(".");
+        let st = vec![Funct(0, ns.name("a")),
+                      Funct(0, ns.name("b")),
+                      Funct(0, ns.name("[]")),
+                      Funct(2, cons),
+                      Funct(2, cons)];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "[a, b]");
+    }
+
+    #[test]
+    fn list_with_explicit_tail() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let st = vec![Var(0), Var(1), Funct(2, ns.name("."))];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "[_G0 | _G1]");
+    }
+
+    #[test]
+    fn variable_names_come_from_the_supplied_map() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let st = vec![Var(0), Var(1), Funct(2, ns.name("foo"))];
+        let st = unsafe { struct_from_vec(st) };
+
+        let mut buf = Vec::new();
+        write(&st, &ops, &["X", "Y"], &mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "foo(X, Y)");
+    }
+
+    #[test]
+    fn atoms_needing_quotes_are_single_quoted() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let st = vec![Funct(0, ns.name("hello world"))];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "'hello world'");
+    }
+
+    #[test]
+    fn strings_are_double_quoted() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let st = vec![Str("say \"hi\"")];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "\"say \\\"hi\\\"\"");
+    }
+
+    #[test]
+    fn whole_number_floats_keep_a_decimal_point() {
+        let ns = NameSpace::new();
+        let ops = OpTable::default(&ns);
+        let st = vec![Float(1.0)];
+        let st = unsafe { struct_from_vec(st) };
+        assert_eq!(render(&st, &ops), "1.0");
+    }
+}